@@ -1,15 +1,108 @@
+use std::fmt;
+use std::slice;
+
 use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 
+pub mod wheel;
+
+pub use wheel::{BUILD_ENV_FILENAME, Wheel, WheelFilename, read_entry_to_string};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BuildEnvMetadata {
     #[serde(with = "chrono_format")]
     pub build_time: DateTime<Utc>,
+    /// Where the list of requested environment variables came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_source: Option<EnvSource>,
+    /// Environment variables that were requested by name but not set at build time.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
     pub git: Option<RepositoryInfo>,
     #[serde(rename = "env")]
-    pub env_vars: IndexMap<String, String>,
+    pub env_vars: IndexMap<String, EnvValue>,
     pub automation: Option<AutomationInfo>,
+    /// Static fields configured for the project.
+    #[serde(default, skip_serializing_if = "toml::Table::is_empty")]
+    pub extra: toml::Table,
+}
+
+impl BuildEnvMetadata {
+    /// Parse a build environment file, written either as TOML or as JSON
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        if content.trim_start().starts_with('{') {
+            Ok(serde_json::from_str(content)?)
+        } else {
+            Ok(toml::from_str(content)?)
+        }
+    }
+}
+
+/// Where the list of requested environment variables came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvSource {
+    /// The built-in whitelist.
+    Default,
+    /// A list file.
+    File,
+    /// A comma-separated list given inline.
+    Inline,
+    /// The `[tool.wheel-metadata-injector]` table of the project's pyproject.toml.
+    Config,
+}
+
+/// The value of a captured environment variable: a string, or a list of strings
+/// for variables that were split when captured
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EnvValue {
+    String(String),
+    List(Vec<String>),
+}
+
+impl EnvValue {
+    /// The string, or the items of the list
+    pub fn as_slice(&self) -> &[String] {
+        match self {
+            EnvValue::String(value) => slice::from_ref(value),
+            EnvValue::List(values) => values,
+        }
+    }
+
+    /// Whether the value equals `expected`, the items of a list being compared
+    /// against `expected` split on commas
+    pub fn matches(&self, expected: &str) -> bool {
+        match self {
+            EnvValue::String(value) => value == expected,
+            EnvValue::List(values) => values.iter().map(String::as_str).eq(expected
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())),
+        }
+    }
+}
+
+impl From<String> for EnvValue {
+    fn from(value: String) -> Self {
+        EnvValue::String(value)
+    }
+}
+
+impl From<&str> for EnvValue {
+    fn from(value: &str) -> Self {
+        EnvValue::String(value.to_string())
+    }
+}
+
+impl fmt::Display for EnvValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvValue::String(value) => f.write_str(value),
+            EnvValue::List(values) => write!(f, "[{}]", values.join(", ")),
+        }
+    }
 }
 
 mod chrono_format {
@@ -48,19 +141,85 @@ mod chrono_format {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct RepositoryInfo {
     /// The git remote URL of the repository.
     #[serde(rename = "url")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
+    /// The URLs of all remotes, by name, when requested.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub remotes: IndexMap<String, String>,
     /// The commit hash of the repository at the time of wheel creation.
     #[serde(rename = "commit")]
     pub commit: String,
+    /// Where the information was read from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<GitSource>,
+    /// The branch checked out, if HEAD was not detached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Tags pointing at the commit.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// `git describe --tags --always --dirty` output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub describe: Option<String>,
+    /// When the commit was made.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_time: Option<DateTime<Utc>>,
+    /// Author of the commit, as `Name <email>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Whether tracked files had uncommitted changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dirty: Option<bool>,
+    /// Tracked paths with uncommitted changes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modified: Vec<String>,
+    /// Commits checked out in submodules, by path.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub submodules: IndexMap<String, String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Where git information was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GitSource {
+    /// The git repository containing the sources.
+    Repository,
+    /// A `.git_archival.txt` file expanded by `git archive`.
+    GitArchival,
+    /// The version and project URLs of an sdist's `PKG-INFO`.
+    PkgInfo,
+    /// The variables of the CI system running the build.
+    Ci,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct AutomationInfo {
+    /// The CI system that built the wheel, e.g. `github-actions` or `gitlab-ci`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Link to the pipeline that built the wheel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline_url: Option<String>,
+    /// Permalink to the run, or the attempt of it, that built the wheel, for
+    /// navigating back to the build logs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_url: Option<String>,
+    /// The ID of the job within the pipeline.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// The attempt number, counting from 1, when the job was retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempt: Option<u32>,
+    /// The user or account that triggered the pipeline.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    /// The branch or tag being built.
+    #[serde(rename = "ref", default, skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
     /// Information specific to github actions.
     #[serde(flatten)]
     pub actions_info: Option<ActionsInfo>,
@@ -0,0 +1,478 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use anyhow::Context;
+use zip::ZipArchive;
+use zip::read::ZipFile;
+
+use crate::BuildEnvMetadata;
+
+/// Name of the build environment file inside the dist-info directory
+pub const BUILD_ENV_FILENAME: &str = "WHEEL.metadata";
+
+/// A wheel archive with its dist-info directory located
+pub struct Wheel<R: Read + Seek> {
+    archive: ZipArchive<R>,
+    dist_info_dir: String,
+}
+
+impl Wheel<File> {
+    /// Open a wheel file, checking its dist-info directory against the filename when
+    /// it follows the wheel naming convention (renamed files like `out.whl` are
+    /// accepted as long as they have exactly one top-level dist-info directory)
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open wheel file: {}", path.display()))?;
+        let filename = WheelFilename::from_path(&path.to_string_lossy());
+
+        Self::new(file, filename.as_ref())
+    }
+}
+
+impl<R: Read + Seek> Wheel<R> {
+    /// Read a wheel from `reader`, checking its dist-info directory against
+    /// `filename` when the wheel's filename is known
+    pub fn new(reader: R, filename: Option<&WheelFilename>) -> anyhow::Result<Self> {
+        let archive = ZipArchive::new(reader).context("Failed to open ZIP archive")?;
+
+        Self::from_archive(archive, filename)
+    }
+
+    pub fn from_archive(
+        archive: ZipArchive<R>,
+        filename: Option<&WheelFilename>,
+    ) -> anyhow::Result<Self> {
+        let dist_info_dir = find_dist_info_dir(archive.file_names(), filename)?;
+
+        Ok(Wheel {
+            archive,
+            dist_info_dir,
+        })
+    }
+
+    /// The dist-info directory name, without trailing slash
+    pub fn dist_info_dir(&self) -> &str {
+        &self.dist_info_dir
+    }
+
+    /// The archive path of `name` inside the dist-info directory
+    pub fn dist_info_path(&self, name: &str) -> String {
+        format!("{}/{}", self.dist_info_dir, name)
+    }
+
+    pub fn archive(&mut self) -> &mut ZipArchive<R> {
+        &mut self.archive
+    }
+
+    pub fn into_archive(self) -> ZipArchive<R> {
+        self.archive
+    }
+
+    /// Read an entry of the archive as a string
+    pub fn read_file(&mut self, path: &str) -> anyhow::Result<String> {
+        let mut file = self
+            .archive
+            .by_name(path)
+            .with_context(|| format!("Failed to find file in archive: {}", path))?;
+
+        read_entry_to_string(&mut file)
+            .with_context(|| format!("Failed to read file from archive: {}", path))
+    }
+
+    /// Read an entry of the dist-info directory, `None` if it does not exist
+    pub fn read_dist_info_file(&mut self, name: &str) -> anyhow::Result<Option<String>> {
+        let path = self.dist_info_path(name);
+        if self.archive.index_for_name(&path).is_none() {
+            return Ok(None);
+        }
+
+        self.read_file(&path).map(Some)
+    }
+
+    pub fn metadata(&mut self) -> anyhow::Result<String> {
+        self.read_required("METADATA")
+    }
+
+    /// The dist-info `WHEEL` file
+    pub fn wheel_file(&mut self) -> anyhow::Result<String> {
+        self.read_required("WHEEL")
+    }
+
+    pub fn record(&mut self) -> anyhow::Result<String> {
+        self.read_required("RECORD")
+    }
+
+    pub fn entry_points(&mut self) -> anyhow::Result<Option<String>> {
+        self.read_dist_info_file("entry_points.txt")
+    }
+
+    /// The raw build environment file, if the wheel has one
+    pub fn build_env(&mut self) -> anyhow::Result<Option<String>> {
+        self.read_dist_info_file(BUILD_ENV_FILENAME)
+    }
+
+    /// The parsed build environment file, if the wheel has one
+    pub fn build_env_metadata(&mut self) -> anyhow::Result<Option<BuildEnvMetadata>> {
+        let Some(content) = self.build_env()? else {
+            return Ok(None);
+        };
+
+        BuildEnvMetadata::parse(&content)
+            .map(Some)
+            .with_context(|| format!("Failed to parse {}", BUILD_ENV_FILENAME))
+    }
+
+    fn read_required(&mut self, name: &str) -> anyhow::Result<String> {
+        self.read_dist_info_file(name)?
+            .with_context(|| format!("Wheel has no {}", self.dist_info_path(name)))
+    }
+}
+
+/// Read an entry as a string, failing if it decompresses to more than the size
+/// declared in the central directory
+pub fn read_entry_to_string<R: Read>(file: &mut ZipFile<R>) -> anyhow::Result<String> {
+    let declared_size = file.size();
+    let mut content = String::new();
+    (&mut *file)
+        .take(declared_size.saturating_add(1))
+        .read_to_string(&mut content)?;
+    if content.len() as u64 > declared_size {
+        anyhow::bail!(
+            "Wheel entry '{}' decompresses to more than its declared size of {} bytes",
+            file.name(),
+            declared_size
+        );
+    }
+
+    Ok(content)
+}
+
+/// The components of a wheel filename,
+/// `{name}-{version}(-{build})?-{python}-{abi}-{platform}.whl`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WheelFilename {
+    pub name: String,
+    pub version: String,
+    pub build: Option<String>,
+    pub python_tag: String,
+    pub abi_tag: String,
+    pub platform_tag: String,
+}
+
+impl WheelFilename {
+    pub fn parse(filename: &str) -> anyhow::Result<Self> {
+        let stem = filename
+            .strip_suffix(".whl")
+            .ok_or_else(|| anyhow::anyhow!("'{}' is not a wheel filename", filename))?;
+
+        let parts: Vec<&str> = stem.split('-').collect();
+        let (name, version, build, tags) = match parts.as_slice() {
+            [name, version, tags @ ..] if tags.len() == 3 => (name, version, None, tags),
+            [name, version, build, tags @ ..] if tags.len() == 3 => {
+                (name, version, Some(build.to_string()), tags)
+            }
+            _ => anyhow::bail!(
+                "Invalid wheel filename '{}', expected {{name}}-{{version}}(-{{build}})?-{{python}}-{{abi}}-{{platform}}.whl",
+                filename
+            ),
+        };
+
+        if parts.iter().any(|part| part.is_empty()) {
+            anyhow::bail!("Invalid wheel filename '{}', empty component", filename);
+        }
+
+        Ok(WheelFilename {
+            name: name.to_string(),
+            version: version.to_string(),
+            build,
+            python_tag: tags[0].to_string(),
+            abi_tag: tags[1].to_string(),
+            platform_tag: tags[2].to_string(),
+        })
+    }
+
+    /// Parse the file name of `path`, `None` if it is not a valid wheel filename
+    pub fn from_path(path: &str) -> Option<Self> {
+        let filename = Path::new(path).file_name()?.to_str()?;
+        Self::parse(filename).ok()
+    }
+
+    /// The dist-info directory this wheel should contain, in normalized form
+    pub fn dist_info_dir(&self) -> String {
+        format!(
+            "{}-{}.dist-info",
+            normalize_dist_name(&self.name),
+            normalize_version(&self.version)
+        )
+    }
+}
+
+/// Normalize a distribution name for comparison: lowercase, with runs of `-`, `_`
+/// and `.` replaced by a single `_`, as in wheel filenames and dist-info names
+pub fn normalize_dist_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    let mut in_separator = false;
+
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !in_separator {
+                normalized.push('_');
+            }
+            in_separator = true;
+        } else {
+            normalized.extend(c.to_lowercase());
+            in_separator = false;
+        }
+    }
+
+    normalized
+}
+
+// Wheel filenames escape `-` in versions as `_`, older dist-info names do not
+fn normalize_version(version: &str) -> String {
+    version.to_lowercase().replace('-', "_")
+}
+
+/// Find the top-level dist-info directory (without trailing slash) among the entry
+/// `names` of a wheel.
+///
+/// The wheel must contain exactly one top-level `.dist-info` directory; ones nested
+/// in package directories (vendored dependencies) are ignored. When `wheel` is given
+/// the directory must also match the name and version from the wheel filename.
+pub fn find_dist_info_dir<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    wheel: Option<&WheelFilename>,
+) -> anyhow::Result<String> {
+    let candidates: BTreeSet<&str> = names
+        .into_iter()
+        .filter_map(|name| name.split_once('/'))
+        .map(|(top_level, _)| top_level)
+        .filter(|top_level| top_level.ends_with(".dist-info"))
+        .collect();
+
+    let dist_info_dir = match candidates.len() {
+        0 => anyhow::bail!("No top-level .dist-info directory found in wheel"),
+        1 => candidates.into_iter().next().unwrap(),
+        _ => anyhow::bail!(
+            "Wheel has {} top-level .dist-info directories, expected exactly one: {}",
+            candidates.len(),
+            candidates.into_iter().collect::<Vec<_>>().join(", ")
+        ),
+    };
+
+    if let Some(wheel) = wheel {
+        let expected = wheel.dist_info_dir();
+        let found = dist_info_dir
+            .strip_suffix(".dist-info")
+            .and_then(|stem| stem.split_once('-'))
+            .map(|(name, version)| {
+                format!(
+                    "{}-{}.dist-info",
+                    normalize_dist_name(name),
+                    normalize_version(version)
+                )
+            });
+
+        if found.as_deref() != Some(expected.as_str()) {
+            anyhow::bail!(
+                "Wheel's dist-info directory '{}' does not match its filename, expected '{}'",
+                dist_info_dir,
+                expected
+            );
+        }
+    }
+
+    Ok(dist_info_dir.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EnvSource, EnvValue};
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    /// `demo-1.0-py3-none-any.whl` holding `files` in its dist-info directory
+    fn demo_wheel(files: &[(&str, &str)]) -> Wheel<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer
+                .start_file(
+                    format!("demo-1.0.dist-info/{}", name),
+                    SimpleFileOptions::default(),
+                )
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        let reader = Cursor::new(writer.finish().unwrap().into_inner());
+
+        let filename = WheelFilename::parse("demo-1.0-py3-none-any.whl").unwrap();
+        Wheel::new(reader, Some(&filename)).unwrap()
+    }
+
+    #[test]
+    fn parses_wheel_filenames() {
+        let wheel = WheelFilename::parse("torch-2.1.0+cu118-cp311-cp311-linux_x86_64.whl").unwrap();
+        assert_eq!(wheel.name, "torch");
+        assert_eq!(wheel.version, "2.1.0+cu118");
+        assert_eq!(wheel.build, None);
+        assert_eq!(wheel.platform_tag, "linux_x86_64");
+
+        let wheel = WheelFilename::parse("My.Package-1.0-1-py3-none-any.whl").unwrap();
+        assert_eq!(wheel.build.as_deref(), Some("1"));
+        assert_eq!(wheel.dist_info_dir(), "my_package-1.0.dist-info");
+
+        assert!(WheelFilename::parse("demo-1.0.whl").is_err());
+        assert!(WheelFilename::parse("demo-1.0-py3-none-any.zip").is_err());
+        assert_eq!(WheelFilename::from_path("/tmp/output.zip"), None);
+        assert_eq!(WheelFilename::from_path("/tmp/out.whl"), None);
+        assert_eq!(
+            WheelFilename::from_path("dist/demo-1.0-py3-none-any.whl").map(|wheel| wheel.name),
+            Some("demo".to_string())
+        );
+    }
+
+    #[test]
+    fn reads_present_and_absent_dist_info_files() {
+        let mut wheel = demo_wheel(&[
+            (
+                "METADATA",
+                "Metadata-Version: 2.1\nName: demo\nVersion: 1.0\n",
+            ),
+            ("WHEEL", "Wheel-Version: 1.0\n"),
+            ("entry_points.txt", "[console_scripts]\ndemo = demo:main\n"),
+            ("RECORD", "demo-1.0.dist-info/RECORD,,\n"),
+        ]);
+
+        assert_eq!(
+            wheel.metadata().unwrap(),
+            "Metadata-Version: 2.1\nName: demo\nVersion: 1.0\n"
+        );
+        assert_eq!(wheel.wheel_file().unwrap(), "Wheel-Version: 1.0\n");
+        assert_eq!(wheel.record().unwrap(), "demo-1.0.dist-info/RECORD,,\n");
+        assert_eq!(
+            wheel.entry_points().unwrap().as_deref(),
+            Some("[console_scripts]\ndemo = demo:main\n")
+        );
+        assert_eq!(wheel.read_dist_info_file("INSTALLER").unwrap(), None);
+        assert_eq!(wheel.build_env().unwrap(), None);
+        assert!(wheel.build_env_metadata().unwrap().is_none());
+
+        let mut wheel = demo_wheel(&[("RECORD", "demo-1.0.dist-info/RECORD,,\n")]);
+        assert_eq!(wheel.entry_points().unwrap(), None);
+        assert_eq!(
+            wheel.metadata().unwrap_err().to_string(),
+            "Wheel has no demo-1.0.dist-info/METADATA"
+        );
+        assert_eq!(
+            wheel.wheel_file().unwrap_err().to_string(),
+            "Wheel has no demo-1.0.dist-info/WHEEL"
+        );
+    }
+
+    #[test]
+    fn parses_toml_build_env_metadata() {
+        let mut wheel = demo_wheel(&[(
+            BUILD_ENV_FILENAME,
+            r#"# Build environment variables captured during wheel creation
+
+build_time = "2023-11-14T22:13:20+00:00"
+env_source = "config"
+missing = ["CUDA_HOME"]
+
+[env]
+CUDA_VERSION = "12.4"
+TORCH_CUDA_ARCH_LIST = ["8.0", "9.0"]
+"#,
+        )]);
+
+        let metadata = wheel.build_env_metadata().unwrap().unwrap();
+        assert_eq!(metadata.build_time.timestamp(), 1700000000);
+        assert_eq!(metadata.env_source, Some(EnvSource::Config));
+        assert_eq!(metadata.missing, ["CUDA_HOME"]);
+        assert_eq!(metadata.env_vars["CUDA_VERSION"], "12.4".into());
+        assert_eq!(
+            metadata.env_vars["TORCH_CUDA_ARCH_LIST"],
+            EnvValue::List(vec!["8.0".to_string(), "9.0".to_string()])
+        );
+        assert!(metadata.git.is_none());
+        assert!(metadata.automation.is_none());
+
+        // Files written before env_source and missing were recorded
+        let mut wheel = demo_wheel(&[(
+            BUILD_ENV_FILENAME,
+            "build_time = \"2023-11-14T22:13:20+00:00\"\n\n[env]\n",
+        )]);
+        let metadata = wheel.build_env_metadata().unwrap().unwrap();
+        assert_eq!(metadata.env_source, None);
+        assert!(metadata.missing.is_empty());
+        assert!(metadata.env_vars.is_empty());
+    }
+
+    #[test]
+    fn parses_json_build_env_metadata() {
+        let mut wheel = demo_wheel(&[(
+            BUILD_ENV_FILENAME,
+            r#"{
+  "build_time": "2023-11-14T22:13:20+00:00",
+  "env_source": "inline",
+  "missing": ["CUDA_HOME", "CUDNN_VERSION"],
+  "git": null,
+  "env": {
+    "CUDA_VERSION": "12.4",
+    "TORCH_CUDA_ARCH_LIST": ["8.0", "9.0"]
+  },
+  "automation": null
+}
+"#,
+        )]);
+
+        let metadata = wheel.build_env_metadata().unwrap().unwrap();
+        assert_eq!(metadata.build_time.timestamp(), 1700000000);
+        assert_eq!(metadata.env_source, Some(EnvSource::Inline));
+        assert_eq!(metadata.missing, ["CUDA_HOME", "CUDNN_VERSION"]);
+        assert_eq!(
+            metadata.env_vars.keys().collect::<Vec<_>>(),
+            ["CUDA_VERSION", "TORCH_CUDA_ARCH_LIST"]
+        );
+        assert_eq!(
+            metadata.env_vars["TORCH_CUDA_ARCH_LIST"],
+            EnvValue::List(vec!["8.0".to_string(), "9.0".to_string()])
+        );
+
+        let mut wheel = demo_wheel(&[(BUILD_ENV_FILENAME, "{\"env\": {}}")]);
+        let err = wheel.build_env_metadata().unwrap_err();
+        assert_eq!(err.to_string(), "Failed to parse WHEEL.metadata");
+    }
+
+    #[test]
+    fn ignores_vendored_dist_info() {
+        let names = [
+            "flash_attn/_vendor/einops-0.7.0.dist-info/METADATA",
+            "flash_attn/__init__.py",
+            "Flash_Attn-2.5.0.dist-info/METADATA",
+            "Flash_Attn-2.5.0.dist-info/RECORD",
+        ];
+        let wheel = WheelFilename::parse("flash_attn-2.5.0-cp311-cp311-linux_x86_64.whl").unwrap();
+
+        assert_eq!(
+            find_dist_info_dir(names, Some(&wheel)).unwrap(),
+            "Flash_Attn-2.5.0.dist-info"
+        );
+    }
+
+    #[test]
+    fn rejects_ambiguous_or_mismatched_dist_info() {
+        let names = ["a-1.0.dist-info/METADATA", "b-1.0.dist-info/METADATA"];
+        assert!(find_dist_info_dir(names, None).is_err());
+
+        let wheel = WheelFilename::parse("b-2.0-py3-none-any.whl").unwrap();
+        assert!(find_dist_info_dir(["b-1.0.dist-info/METADATA"], Some(&wheel)).is_err());
+
+        assert!(find_dist_info_dir(["b/__init__.py"], None).is_err());
+    }
+}
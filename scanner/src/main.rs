@@ -4,6 +4,7 @@ use bytes::Bytes;
 
 use cel_interpreter::{Context as CELContext, Program};
 use clap::{ArgAction, Parser};
+use common::{Wheel, WheelFilename, BUILD_ENV_FILENAME};
 
 use google_cloud_storage::client::{Client, ClientConfig};
 use google_cloud_storage::http::objects::download::Range;
@@ -17,7 +18,6 @@ use zip::read::ZipArchive;
 
 mod zip_utils;
 
-const BUILD_ENV_FILENAME: &str = "WHEEL.metadata";
 const CHUNK_SIZE: u64 = 16 * 1024 * 1024; // 16MB chunk for better caching
 const END_OF_ZIP_BUFFER: u64 = 64 * 1024; // 64KB buffer for ZIP central directory
 
@@ -30,10 +30,15 @@ struct Args {
     wheel_path: String,
 
     /// Environment variable to validate is in the build metadata, a key value pair
-    /// in the form of KEY=VALUE
+    /// in the form of KEY=VALUE. List values are compared as comma-separated items
     #[clap(short, long, value_parser=parse_key_val, action = ArgAction::Append)]
     env_var: Vec<(String, String)>,
 
+    /// Environment variable that must be recorded as requested but unset at build
+    /// time
+    #[clap(short, long, value_parser, action = ArgAction::Append)]
+    missing: Vec<String>,
+
     /// CEL expression to validate is in the build metadata
     #[clap(short, long, value_parser)]
     cel_expr: Option<String>,
@@ -50,6 +55,53 @@ fn parse_key_val(s: &str) -> Result<(String, String), String> {
     Ok((s[..pos].to_string(), s[pos + 1..].to_string()))
 }
 
+/// Parse the wheel filename from the last path segment of a local path, HTTP URL or
+/// GCS URI, so the dist-info directory can be checked against it. `None` if the
+/// segment is not a valid wheel filename, e.g. for a wheel saved as `out.whl`.
+fn wheel_filename_from_location(location: &str) -> Option<WheelFilename> {
+    let is_url = location.starts_with("http://") || location.starts_with("https://");
+    let path = if is_url {
+        location.split(['?', '#']).next().unwrap_or(location)
+    } else {
+        location
+    };
+
+    let segment = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    if !segment.ends_with(".whl") {
+        return None;
+    }
+
+    // Registries percent-encode the `+` of local versions, e.g. torch-2.1.0%2Bcu118
+    let filename = if is_url {
+        percent_decode(segment)
+    } else {
+        segment.to_string()
+    };
+
+    WheelFilename::parse(&filename).ok()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok());
+        if let (b'%', Some(Ok(byte))) = (bytes[i], hex.map(|hex| u8::from_str_radix(hex, 16))) {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt()
@@ -93,8 +145,18 @@ async fn main() -> Result<()> {
     };
 
     let metadata = metadata?;
-    let metadata: common::BuildEnvMetadata =
-        toml::from_str(&metadata).with_context(|| "Failed to parse metadata as TOML")?;
+    let metadata = common::BuildEnvMetadata::parse(&metadata)
+        .with_context(|| format!("Failed to parse {}", BUILD_ENV_FILENAME))?;
+
+    if let Some(automation) = &metadata.automation {
+        if let Some(run_url) = &automation.run_url {
+            tracing::info!(
+                "Built by {} run: {}",
+                automation.provider.as_deref().unwrap_or("CI"),
+                run_url
+            );
+        }
+    }
 
     if let Some(cel_expr) = args.cel_expr {
         tracing::debug!("Evaluating CEL expression: {}", cel_expr);
@@ -123,7 +185,7 @@ async fn main() -> Result<()> {
     let mut num_missing = 0;
     for (key, value) in args.env_var {
         if let Some(env_value) = metadata.env_vars.get(&key) {
-            if env_value != &value {
+            if !env_value.matches(&value) {
                 tracing::warn!(
                     "Environment variable {} does not match! Expected: {}, Found: {}",
                     key,
@@ -140,6 +202,21 @@ async fn main() -> Result<()> {
         }
     }
 
+    for key in args.missing {
+        if metadata.missing.contains(&key) {
+            tracing::info!("Environment variable {} was unset at build time!", key);
+        } else if metadata.env_vars.contains_key(&key) {
+            tracing::warn!("Environment variable {} was set at build time!", key);
+            num_missing += 1;
+        } else {
+            tracing::warn!(
+                "Environment variable {} was not requested at build time!",
+                key
+            );
+            num_missing += 1;
+        }
+    }
+
     if num_missing > 0 {
         tracing::error!(
             "Found {} missing or mismatched environment variables!",
@@ -160,30 +237,26 @@ async fn main() -> Result<()> {
 /// Helper function to extract metadata from a ZIP archive
 #[tracing::instrument(skip_all)]
 async fn extract_metadata_from_archive<R: Read + Seek>(
-    mut archive: ZipArchive<R>,
-    _source_desc: &str,
+    archive: ZipArchive<R>,
+    filename: Option<&WheelFilename>,
 ) -> Result<String> {
     // Find the dist-info directory
-    let dist_info_dir = zip_utils::find_dist_info_dir(&mut archive)
+    let mut wheel = Wheel::from_archive(archive, filename)
         .with_context(|| "Failed to find .dist-info directory in wheel")?;
 
-    tracing::info!("Found dist-info directory: {}", dist_info_dir);
+    tracing::info!("Found dist-info directory: {}/", wheel.dist_info_dir());
 
     // Extract and output build environment metadata if it exists
-    let metadata_path = format!("{}{}", dist_info_dir, BUILD_ENV_FILENAME);
-
-    let has_metadata = archive
-        .by_name(&metadata_path)
-        .map(|file| {
-            tracing::info!("Found {} ({} bytes)", BUILD_ENV_FILENAME, file.size());
-            true
-        })
-        .unwrap_or(false);
-
-    if has_metadata {
-        let metadata_content = zip_utils::read_file_as_string(&mut archive, &metadata_path)
-            .with_context(|| format!("Failed to read {}", BUILD_ENV_FILENAME))?;
-
+    let metadata_content = wheel
+        .build_env()
+        .with_context(|| format!("Failed to read {}", BUILD_ENV_FILENAME))?;
+
+    if let Some(metadata_content) = metadata_content {
+        tracing::info!(
+            "Found {} ({} bytes)",
+            BUILD_ENV_FILENAME,
+            metadata_content.len()
+        );
         tracing::info!("=== Build Environment Metadata ===");
         println!("{}", metadata_content);
 
@@ -199,6 +272,7 @@ async fn extract_metadata_from_archive<R: Read + Seek>(
 /// Extract metadata from a local wheel file
 #[tracing::instrument(skip_all)]
 async fn extract_from_local_file(wheel_path: &str) -> Result<String> {
+    let filename = wheel_filename_from_location(wheel_path);
     tracing::info!("Reading local wheel file: {}", wheel_path);
 
     let file = std::fs::File::open(wheel_path)
@@ -207,13 +281,14 @@ async fn extract_from_local_file(wheel_path: &str) -> Result<String> {
     let archive = ZipArchive::new(file)
         .with_context(|| format!("Failed to open ZIP archive: {}", wheel_path))?;
 
-    extract_metadata_from_archive(archive, "local file").await
+    extract_metadata_from_archive(archive, filename.as_ref()).await
 }
 
 /// Do a ranged read from a pypy registry URL, this is downloading just the metadata
 /// part of the wheel using a standard HTTP request
 #[tracing::instrument(skip_all)]
 async fn extract_from_registry(uri: &str) -> Result<String> {
+    let filename = wheel_filename_from_location(uri);
     tracing::info!("Fetching wheel from registry: {}", uri);
     let client = reqwest::Client::new();
 
@@ -231,11 +306,12 @@ async fn extract_from_registry(uri: &str) -> Result<String> {
     let http_reader = HttpRangedReader::new(client, uri.to_string(), total_size).await?;
     let archive = ZipArchive::new(http_reader).context("Failed to open ZIP archive from HTTP")?;
 
-    extract_metadata_from_archive(archive, "HTTP source").await
+    extract_metadata_from_archive(archive, filename.as_ref()).await
 }
 
 #[tracing::instrument(skip_all)]
 async fn extract_from_cloud(uri: &str) -> Result<String> {
+    let filename = wheel_filename_from_location(uri);
     let uri = uri
         .strip_prefix("gs://")
         .context("URI must start with gs://")?;
@@ -257,7 +333,7 @@ async fn extract_from_cloud(uri: &str) -> Result<String> {
 
     let archive = ZipArchive::new(gcs_reader).context("Failed to open ZIP archive from GCS")?;
 
-    extract_metadata_from_archive(archive, "GCS source").await
+    extract_metadata_from_archive(archive, filename.as_ref()).await
 }
 
 /// Extract dependency version from any wheel source (local, HTTP, or GCS)
@@ -283,15 +359,16 @@ async fn extract_dependency_from_local_file(
     wheel_path: &str,
     dependency_name: &str,
 ) -> Result<Option<String>> {
+    let filename = wheel_filename_from_location(wheel_path);
     tracing::info!("Reading local wheel file for dependency: {}", wheel_path);
 
     let file = std::fs::File::open(wheel_path)
         .with_context(|| format!("Failed to open wheel file: {}", wheel_path))?;
 
-    let mut archive = ZipArchive::new(file)
+    let archive = ZipArchive::new(file)
         .with_context(|| format!("Failed to open ZIP archive: {}", wheel_path))?;
 
-    extract_dependency_from_archive(&mut archive, dependency_name).await
+    extract_dependency_from_archive(archive, filename.as_ref(), dependency_name).await
 }
 
 /// Extract dependency version from a registry URL
@@ -300,6 +377,7 @@ async fn extract_dependency_from_registry(
     uri: &str,
     dependency_name: &str,
 ) -> Result<Option<String>> {
+    let filename = wheel_filename_from_location(uri);
     tracing::info!("Fetching wheel from registry for dependency: {}", uri);
     let client = reqwest::Client::new();
 
@@ -314,15 +392,15 @@ async fn extract_dependency_from_registry(
     tracing::info!("Wheel size: {} bytes", total_size);
 
     let http_reader = HttpRangedReader::new(client, uri.to_string(), total_size).await?;
-    let mut archive =
-        ZipArchive::new(http_reader).context("Failed to open ZIP archive from HTTP")?;
+    let archive = ZipArchive::new(http_reader).context("Failed to open ZIP archive from HTTP")?;
 
-    extract_dependency_from_archive(&mut archive, dependency_name).await
+    extract_dependency_from_archive(archive, filename.as_ref(), dependency_name).await
 }
 
 /// Extract dependency version from cloud storage
 #[tracing::instrument(skip_all)]
 async fn extract_dependency_from_cloud(uri: &str, dependency_name: &str) -> Result<Option<String>> {
+    let filename = wheel_filename_from_location(uri);
     let uri = uri
         .strip_prefix("gs://")
         .context("URI must start with gs://")?;
@@ -341,25 +419,26 @@ async fn extract_dependency_from_cloud(uri: &str, dependency_name: &str) -> Resu
 
     let gcs_reader =
         GcsRangedReader::new(client, bucket.to_string(), object_path.to_string()).await?;
-    let mut archive = ZipArchive::new(gcs_reader).context("Failed to open ZIP archive from GCS")?;
+    let archive = ZipArchive::new(gcs_reader).context("Failed to open ZIP archive from GCS")?;
 
-    extract_dependency_from_archive(&mut archive, dependency_name).await
+    extract_dependency_from_archive(archive, filename.as_ref(), dependency_name).await
 }
 
 /// Helper function to extract dependency version from a ZIP archive
 #[tracing::instrument(skip_all)]
 async fn extract_dependency_from_archive<R: Read + Seek>(
-    archive: &mut ZipArchive<R>,
+    archive: ZipArchive<R>,
+    filename: Option<&WheelFilename>,
     dependency_name: &str,
 ) -> Result<Option<String>> {
     // Find the dist-info directory
-    let dist_info_dir = zip_utils::find_dist_info_dir(archive)
+    let mut wheel = Wheel::from_archive(archive, filename)
         .with_context(|| "Failed to find .dist-info directory in wheel")?;
 
-    tracing::info!("Found dist-info directory: {}", dist_info_dir);
+    tracing::info!("Found dist-info directory: {}/", wheel.dist_info_dir());
 
     // Extract dependency version from METADATA file
-    zip_utils::extract_dependency_version(archive, &dist_info_dir, dependency_name)
+    zip_utils::extract_dependency_version(&mut wheel, dependency_name)
 }
 
 /// Trait for common functionality between different ranged readers
@@ -847,3 +926,101 @@ impl Seek for HttpRangedReader {
         Ok(self.position)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Name and version of the wheel at `location`
+    fn parsed(location: &str) -> Option<(String, String)> {
+        wheel_filename_from_location(location).map(|wheel| (wheel.name, wheel.version))
+    }
+
+    fn torch() -> Option<(String, String)> {
+        Some(("torch".to_string(), "2.1.0+cu118".to_string()))
+    }
+
+    #[test]
+    fn decodes_local_versions_in_urls() {
+        assert_eq!(
+            parsed("https://download.pytorch.org/whl/cu118/torch-2.1.0%2Bcu118-cp311-cp311-linux_x86_64.whl"),
+            torch()
+        );
+        assert_eq!(
+            parsed("https://download.pytorch.org/whl/cu118/torch-2.1.0%2bcu118-cp311-cp311-linux_x86_64.whl"),
+            torch()
+        );
+        assert_eq!(
+            parsed("http://localhost:8080/torch-2.1.0+cu118-cp311-cp311-linux_x86_64.whl"),
+            torch()
+        );
+    }
+
+    #[test]
+    fn ignores_query_strings_and_fragments() {
+        let demo = Some(("demo".to_string(), "1.0".to_string()));
+        assert_eq!(
+            parsed("https://files.example.com/demo-1.0-py3-none-any.whl?X-Amz-Signature=abc%2F"),
+            demo
+        );
+        assert_eq!(
+            parsed("https://files.example.com/demo-1.0-py3-none-any.whl#sha256=0123abcd"),
+            demo
+        );
+        assert_eq!(
+            parsed("https://files.example.com/demo-1.0-py3-none-any.whl?a=1#sha256=0123abcd"),
+            demo
+        );
+        // Only the path is looked at, not a file name given as a parameter
+        assert_eq!(
+            parsed("https://files.example.com/download?file=demo-1.0-py3-none-any.whl"),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_cloud_storage_uris_and_local_paths() {
+        // Object names and paths are taken literally, they are not percent-encoded
+        assert_eq!(
+            parsed("gs://wheels/cu118/torch-2.1.0+cu118-cp311-cp311-linux_x86_64.whl"),
+            torch()
+        );
+        assert_eq!(
+            parsed("s3://wheels/cu118/torch-2.1.0+cu118-cp311-cp311-linux_x86_64.whl"),
+            torch()
+        );
+        assert_eq!(
+            parsed("gs://wheels/torch-2.1.0%2Bcu118-cp311-cp311-linux_x86_64.whl")
+                .map(|(_, version)| version),
+            Some("2.1.0%2Bcu118".to_string())
+        );
+        assert_eq!(
+            parsed("dist/#1/torch-2.1.0+cu118-cp311-cp311-linux_x86_64.whl"),
+            torch()
+        );
+        assert_eq!(
+            parsed("C:\\dist\\torch-2.1.0+cu118-cp311-cp311-linux_x86_64.whl"),
+            torch()
+        );
+    }
+
+    #[test]
+    fn rejects_non_wheel_file_names() {
+        assert_eq!(parsed("dist/out.whl"), None);
+        assert_eq!(parsed("gs://wheels/latest.whl"), None);
+        assert_eq!(parsed("https://files.example.com/demo-1.0.tar.gz"), None);
+        assert_eq!(parsed("https://files.example.com/simple/demo/"), None);
+        assert_eq!(parsed("dist/demo-1.0-py3-none-any.zip"), None);
+        assert_eq!(parsed("demo-1.0-py3-none-any.whl.part"), None);
+    }
+
+    #[test]
+    fn keeps_malformed_percent_escapes() {
+        assert_eq!(percent_decode("torch-2.1.0%2Bcu118"), "torch-2.1.0+cu118");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%2"), "%2");
+        assert_eq!(percent_decode("%zz%2B"), "%zz+");
+        assert_eq!(percent_decode("%%2B"), "%+");
+        assert_eq!(percent_decode("%E9t%C3%A9"), "\u{FFFD}t\u{e9}");
+    }
+}
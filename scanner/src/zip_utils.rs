@@ -1,49 +1,18 @@
 use anyhow::{Context, Result};
+use common::Wheel;
 use std::io::{Read, Seek};
-use zip::read::ZipArchive;
-
-/// Extract and read a specific file from a ZIP archive as a string
-pub fn read_file_as_string<R: Read + Seek>(
-    archive: &mut ZipArchive<R>,
-    path: &str,
-) -> Result<String> {
-    let mut file = archive
-        .by_name(path)
-        .with_context(|| format!("Failed to find file in archive: {}", path))?;
-
-    let mut content = String::new();
-    file.read_to_string(&mut content)
-        .with_context(|| format!("Failed to read file from archive: {}", path))?;
-
-    Ok(content)
-}
-
-/// Find the dist-info directory in a wheel archive
-pub fn find_dist_info_dir<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Option<String> {
-    for i in 0..archive.len() {
-        if let Ok(file) = archive.by_index(i) {
-            let filename = file.name();
-            // look for files within .dist-info directories
-            if let Some(dist_info_pos) = filename.find(".dist-info/") {
-                let dist_info_dir = &filename[..dist_info_pos + 10]; // +10 for ".dist-info"
-                return Some(format!("{}/", dist_info_dir));
-            }
-        }
-    }
-
-    None
-}
 
 /// Parse wheel METADATA file and extract dependency version
 pub fn extract_dependency_version<R: Read + Seek>(
-    archive: &mut ZipArchive<R>,
-    dist_info_dir: &str,
+    wheel: &mut Wheel<R>,
     dependency_name: &str,
 ) -> Result<Option<String>> {
-    let metadata_path = format!("{}METADATA", dist_info_dir);
-
-    let metadata_content = read_file_as_string(archive, &metadata_path)
-        .with_context(|| format!("Failed to read METADATA file: {}", metadata_path))?;
+    let metadata_content = wheel.metadata().with_context(|| {
+        format!(
+            "Failed to read METADATA file: {}",
+            wheel.dist_info_path("METADATA")
+        )
+    })?;
 
     for line in metadata_content.lines() {
         if line.starts_with("Requires-Dist:") {
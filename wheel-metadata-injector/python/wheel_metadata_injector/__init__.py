@@ -2,18 +2,30 @@ from ._wheel_metadata_injector import (
     process_wheel,
     process_wheel_with_env_file,
     process_wheel_with_env_vars,
+    process_wheels,
+    expand_wheel_paths,
+    preview_wheel,
     get_whitelisted_env_vars,
     get_whitelisted_env_vars_with_file,
     get_env_vars_from_comma_list,
+    load_project_config,
+    CapturedEnv,
+    ProjectConfig,
 )
 
 __all__ = [
     "process_wheel",
     "process_wheel_with_env_file",
     "process_wheel_with_env_vars",
+    "process_wheels",
+    "expand_wheel_paths",
+    "preview_wheel",
     "get_whitelisted_env_vars",
     "get_whitelisted_env_vars_with_file",
     "get_env_vars_from_comma_list",
+    "load_project_config",
+    "CapturedEnv",
+    "ProjectConfig",
 ]
 
 try:
@@ -4,9 +4,13 @@ from . import (
     process_wheel,
     process_wheel_with_env_file,
     process_wheel_with_env_vars,
+    process_wheels,
+    expand_wheel_paths,
+    preview_wheel,
     get_whitelisted_env_vars,
     get_whitelisted_env_vars_with_file,
     get_env_vars_from_comma_list,
+    load_project_config,
 )
 
 
@@ -14,36 +18,166 @@ def main():
     parser = argparse.ArgumentParser(
         description="Inject build environment variables into Python wheel packages"
     )
-    parser.add_argument("wheel", help="Path to the wheel file to process")
     parser.add_argument(
-        "-o", "--output", help="Output file path (default: overwrites input)"
+        "wheels",
+        nargs="+",
+        help="Wheel files to process, directories containing wheels, or glob patterns",
+    )
+    output_group = parser.add_mutually_exclusive_group()
+    output_group.add_argument(
+        "-o",
+        "--output",
+        help="Output file path when processing a single wheel (default: overwrites input)",
+    )
+    output_group.add_argument(
+        "--output-dir",
+        help="Directory to write the processed wheels to (default: overwrites inputs)",
+    )
+    parser.add_argument(
+        "-j",
+        "--jobs",
+        type=int,
+        help="Number of wheels to process in parallel (default: number of CPUs)",
     )
     parser.add_argument(
         "-e",
         "--env-file",
         help="Path to file containing list of environment variables to collect",
     )
+    parser.add_argument(
+        "--lenient-env-file",
+        action="store_true",
+        help="Fall back to the default whitelist if the env file cannot be read "
+        "or is invalid, instead of failing",
+    )
     parser.add_argument(
         "-v",
         "--env-vars",
-        help="Comma-separated list of environment variables to collect",
+        help="Comma-separated list of environment variables to collect, which may "
+        "contain CUDA_* globs, re: regular expressions and ! negations",
+    )
+    parser.add_argument(
+        "--reproducible",
+        action="store_true",
+        help="Produce reproducible output using SOURCE_DATE_EPOCH "
+        "(implied when SOURCE_DATE_EPOCH is set)",
+    )
+    parser.add_argument(
+        "--existing",
+        choices=["replace", "merge", "fail"],
+        help="What to do if the wheel already contains WHEEL.metadata "
+        "(default: replace)",
+    )
+    parser.add_argument(
+        "--verify-record",
+        choices=["strict", "warn", "off"],
+        help="Check RECORD hashes and sizes against the wheel contents before and "
+        "after injection; 'warn' reports mismatches without failing (default: strict)",
+    )
+    parser.add_argument(
+        "--redact",
+        choices=["mask", "refuse", "off"],
+        help="What to do with variables whose name or value looks like a secret "
+        "(default: mask)",
+    )
+    parser.add_argument(
+        "--allow-secret",
+        action="append",
+        default=[],
+        metavar="NAME",
+        help="Record this variable (or * pattern) verbatim even if it looks like a "
+        "secret; may be repeated",
+    )
+    parser.add_argument(
+        "--git-remote",
+        action="append",
+        metavar="NAME",
+        help="Record the URL of this git remote, in order of preference if repeated "
+        "(default: origin, then upstream)",
+    )
+    parser.add_argument(
+        "--all-remotes",
+        action="store_true",
+        help="Also record the URLs of all git remotes",
+    )
+    parser.add_argument(
+        "--source-dir",
+        help="Directory of the sources the wheels were built from, where git "
+        "information is read (default: the working directory)",
+    )
+    parser.add_argument(
+        "--no-config",
+        action="store_true",
+        help="Ignore the [tool.wheel-metadata-injector] table of the project's "
+        "pyproject.toml",
+    )
+    parser.add_argument(
+        "--max-total-size",
+        type=int,
+        metavar="BYTES",
+        help="Maximum total uncompressed size of the wheel contents, in bytes",
+    )
+    parser.add_argument(
+        "--max-compression-ratio",
+        type=int,
+        help="Maximum compression ratio of a single wheel entry",
+    )
+    parser.add_argument(
+        "--dry-run",
+        action="store_true",
+        help="Print the WHEEL.metadata content and RECORD line that would be "
+        "written, without modifying any wheel",
     )
 
     args = parser.parse_args()
 
-    wheel_path = args.wheel
     output_path = args.output
     env_file = args.env_file
     env_vars_list = args.env_vars
 
-    print(f"Processing wheel: {wheel_path}")
+    if output_path and len(args.wheels) > 1:
+        parser.error("--output can only be used with a single wheel, use --output-dir")
+
+    # Flags that were not given fall back to the project configuration
+    args.reproducible = args.reproducible or None
+    args.all_remotes = args.all_remotes or None
+    args.config = None
+    if not args.no_config:
+        try:
+            args.config = load_project_config()
+        except (OSError, ValueError) as e:
+            print(f"Error: {e}", file=sys.stderr)
+            return 1
+    if args.config is not None:
+        print(f"Using configuration from {args.config.path}")
+
+    config_env_vars = None
+    if args.config is not None and not env_vars_list and not env_file:
+        try:
+            config_env_vars = args.config.collect_env_vars()
+        except (OSError, ValueError) as e:
+            print(f"Error: {e}", file=sys.stderr)
+            return 1
 
     if env_vars_list:
         print(f"Using inline environment variable list: {env_vars_list}")
-        env_vars = get_env_vars_from_comma_list(env_vars_list)
+        try:
+            env_vars = get_env_vars_from_comma_list(env_vars_list)
+        except ValueError as e:
+            print(f"Error: {e}", file=sys.stderr)
+            return 1
     elif env_file:
         print(f"Reading environment variable names from file: {env_file}")
-        env_vars = get_whitelisted_env_vars_with_file(env_file)
+        try:
+            env_vars = get_whitelisted_env_vars_with_file(
+                env_file, not args.lenient_env_file
+            )
+        except (OSError, ValueError) as e:
+            print(f"Error: {e}", file=sys.stderr)
+            return 1
+    elif config_env_vars is not None:
+        print("Using environment variable list from project configuration")
+        env_vars = config_env_vars
     else:
         print("Using default whitelisted environment variables")
         env_vars = get_whitelisted_env_vars()
@@ -55,15 +189,44 @@ def main():
         for name, _ in env_vars:
             print(f"  {name}")
 
+    options = dict(
+        reproducible=args.reproducible,
+        existing=args.existing,
+        verify_record=args.verify_record,
+        redact=args.redact,
+        allow_secrets=args.allow_secret,
+        git_remotes=args.git_remote,
+        all_remotes=args.all_remotes,
+        source_dir=args.source_dir,
+        max_total_size=args.max_total_size,
+        max_compression_ratio=args.max_compression_ratio,
+        config=args.config,
+    )
+
+    if args.dry_run:
+        return preview(args.wheels, env_vars, options)
+
+    if len(args.wheels) > 1 or args.output_dir:
+        return process_batch(args, env_vars, options)
+
+    wheel_path = args.wheels[0]
+    print(f"Processing wheel: {wheel_path}")
+
     try:
         if env_vars_list:
             output_path = process_wheel_with_env_vars(
-                wheel_path, env_vars_list, output_path
+                wheel_path, env_vars_list, output_path, **options
             )
         elif env_file:
-            output_path = process_wheel_with_env_file(wheel_path, env_file, output_path)
+            output_path = process_wheel_with_env_file(
+                wheel_path,
+                env_file,
+                output_path,
+                strict=not args.lenient_env_file,
+                **options,
+            )
         else:
-            output_path = process_wheel(wheel_path, output_path)
+            output_path = process_wheel(wheel_path, output_path, **options)
 
         print(f"Successfully processed wheel: {output_path}")
         return 0
@@ -72,5 +235,59 @@ def main():
         return 1
 
 
+def preview(wheels, env_vars, options):
+    try:
+        wheels = expand_wheel_paths(wheels)
+    except ValueError as e:
+        print(f"Error: {e}", file=sys.stderr)
+        return 1
+
+    failed = 0
+    for wheel_path in wheels:
+        try:
+            content, record_line = preview_wheel(wheel_path, env_vars, **options)
+        except Exception as e:
+            failed += 1
+            print(f"Error: {e}", file=sys.stderr)
+            continue
+
+        print(f"=== {wheel_path} ===")
+        print("Would write WHEEL.metadata:")
+        print(content, end="")
+        print("Would add RECORD line:")
+        print(record_line)
+
+    return 1 if failed else 0
+
+
+def process_batch(args, env_vars, options):
+    try:
+        results = process_wheels(
+            args.wheels,
+            env_vars,
+            output_dir=args.output_dir,
+            jobs=args.jobs,
+            **options,
+        )
+    except Exception as e:
+        print(f"Error: {e}", file=sys.stderr)
+        return 1
+
+    print()
+    failed = 0
+    for wheel_path, output_path, error in results:
+        if error is None:
+            print(f"  OK      {wheel_path} -> {output_path}")
+        else:
+            failed += 1
+            print(f"  FAILED  {wheel_path}: {error}")
+    print(
+        f"Processed {len(results)} wheels: "
+        f"{len(results) - failed} succeeded, {failed} failed"
+    )
+
+    return 1 if failed else 0
+
+
 if __name__ == "__main__":
     sys.exit(main())
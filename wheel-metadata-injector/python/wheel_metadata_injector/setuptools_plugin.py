@@ -6,6 +6,7 @@ from . import (
     process_wheel_with_env_file,
     get_whitelisted_env_vars,
     get_whitelisted_env_vars_with_file,
+    load_project_config,
 )
 
 
@@ -60,6 +61,13 @@ class InjectMetadataBdistWheel(bdist_wheel):
 
         print(f"Injecting environment metadata into {wheel_path}")
 
+        config = load_project_config()
+        config_env_vars = None
+        if config is not None:
+            print(f"Using configuration from {config.path}")
+            if not self.env_vars and not self.env_file:
+                config_env_vars = config.collect_env_vars()
+
         temp_env_file = None
 
         try:
@@ -82,6 +90,9 @@ class InjectMetadataBdistWheel(bdist_wheel):
             elif self.env_file:
                 print(f"Reading environment variable names from file: {self.env_file}")
                 env_vars = get_whitelisted_env_vars_with_file(self.env_file)
+            elif config_env_vars is not None:
+                print("Using environment variable list from project configuration")
+                env_vars = config_env_vars
             else:
                 print("Using default whitelisted environment variables")
                 env_vars = get_whitelisted_env_vars()
@@ -95,11 +106,15 @@ class InjectMetadataBdistWheel(bdist_wheel):
 
             try:
                 if self.env_vars and temp_env_file:
-                    process_wheel_with_env_file(wheel_path, temp_env_file, None)
+                    process_wheel_with_env_file(
+                        wheel_path, temp_env_file, None, config=config
+                    )
                 elif self.env_file:
-                    process_wheel_with_env_file(wheel_path, self.env_file, None)
+                    process_wheel_with_env_file(
+                        wheel_path, self.env_file, None, config=config
+                    )
                 else:
-                    process_wheel(wheel_path, None)
+                    process_wheel(wheel_path, None, config=config)
                 print(f"Successfully injected environment metadata into {wheel_path}")
             except Exception as e:
                 print(f"Error injecting environment metadata: {e}")
@@ -0,0 +1,303 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use anyhow::Context;
+
+use crate::{CapturedEnv, InjectOptions, Injection, internal_process_wheel};
+
+/// The outcome of processing one wheel of a batch
+#[derive(Debug)]
+pub struct BatchResult {
+    pub wheel_path: PathBuf,
+    pub output_path: PathBuf,
+    pub result: anyhow::Result<Injection>,
+}
+
+/// Expand wheel paths given on the command line: directories are searched (not
+/// recursively) and glob patterns are matched against the filesystem for `.whl`
+/// files, anything else is taken as a wheel path as is. Duplicates are dropped.
+pub fn expand_wheel_paths(inputs: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut wheel_paths = Vec::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+
+        if path.is_dir() {
+            let mut wheels = Vec::new();
+            for entry in
+                fs::read_dir(path).with_context(|| format!("Failed to read directory {}", input))?
+            {
+                let entry_path = entry?.path();
+                if is_wheel_file(&entry_path) {
+                    wheels.push(entry_path);
+                }
+            }
+            if wheels.is_empty() {
+                anyhow::bail!("No wheels found in directory {}", input);
+            }
+            wheels.sort();
+            wheel_paths.extend(wheels);
+        } else if !path.exists() && input.contains(['*', '?', '[']) {
+            let mut wheels = Vec::new();
+            for entry in
+                glob::glob(input).with_context(|| format!("Invalid glob pattern '{}'", input))?
+            {
+                let entry_path = entry?;
+                if is_wheel_file(&entry_path) {
+                    wheels.push(entry_path);
+                }
+            }
+            if wheels.is_empty() {
+                anyhow::bail!("No wheels match '{}'", input);
+            }
+            wheel_paths.extend(wheels);
+        } else {
+            wheel_paths.push(path.to_path_buf());
+        }
+    }
+
+    let mut seen = HashSet::new();
+    wheel_paths.retain(|path| seen.insert(path.clone()));
+
+    Ok(wheel_paths)
+}
+
+fn is_wheel_file(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext == "whl")
+}
+
+/// Process `wheel_paths` on a pool of `jobs` worker threads, writing each output to
+/// `output_dir` under the wheel's filename, or over the input if `output_dir` is
+/// `None`. A failing wheel does not stop the others; results are returned in the
+/// order of `wheel_paths`.
+pub fn process_wheels(
+    wheel_paths: &[PathBuf],
+    output_dir: Option<&Path>,
+    env: &CapturedEnv,
+    options: &InjectOptions,
+    jobs: usize,
+) -> anyhow::Result<Vec<BatchResult>> {
+    let mut output_paths = Vec::with_capacity(wheel_paths.len());
+    for wheel_path in wheel_paths {
+        let output_path = match output_dir {
+            Some(output_dir) => {
+                let filename = wheel_path
+                    .file_name()
+                    .with_context(|| format!("Invalid wheel path {}", wheel_path.display()))?;
+                output_dir.join(filename)
+            }
+            None => wheel_path.clone(),
+        };
+        if output_paths.contains(&output_path) {
+            anyhow::bail!(
+                "More than one wheel would be written to {}",
+                output_path.display()
+            );
+        }
+        output_paths.push(output_path);
+    }
+
+    if let Some(output_dir) = output_dir {
+        fs::create_dir_all(output_dir).with_context(|| {
+            format!("Failed to create output directory {}", output_dir.display())
+        })?;
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<anyhow::Result<Injection>>>> =
+        Mutex::new(wheel_paths.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, wheel_paths.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= wheel_paths.len() {
+                        break;
+                    }
+
+                    let result = internal_process_wheel(
+                        &wheel_paths[i].to_string_lossy(),
+                        &output_paths[i].to_string_lossy(),
+                        env,
+                        options,
+                    );
+                    results.lock().unwrap()[i] = Some(result);
+                }
+            });
+        }
+    });
+
+    Ok(wheel_paths
+        .iter()
+        .zip(output_paths)
+        .zip(results.into_inner().unwrap())
+        .map(|((wheel_path, output_path), result)| BatchResult {
+            wheel_path: wheel_path.clone(),
+            output_path,
+            result: result.expect("every wheel is processed by a worker"),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Write;
+
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+    use crate::record::RecordEntry;
+
+    /// Write an empty `{name}-1.0` wheel to `dir`
+    fn write_wheel(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(format!("{}-1.0-py3-none-any.whl", name));
+        let init = format!("{}/__init__.py", name);
+        let record = format!(
+            "{}\n{}-1.0.dist-info/RECORD,,\n",
+            RecordEntry::for_data(&init, b"").to_line().unwrap(),
+            name
+        );
+
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        writer
+            .start_file(init, SimpleFileOptions::default())
+            .unwrap();
+        writer
+            .start_file(
+                format!("{}-1.0.dist-info/RECORD", name),
+                SimpleFileOptions::default(),
+            )
+            .unwrap();
+        writer.write_all(record.as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        path
+    }
+
+    fn to_string(path: &Path) -> String {
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn expands_directories_and_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        let beta = write_wheel(dir.path(), "beta");
+        let alpha = write_wheel(dir.path(), "alpha");
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        write_wheel(&dir.path().join("nested"), "gamma");
+
+        let from_dir = expand_wheel_paths(&[to_string(dir.path())]).unwrap();
+        assert_eq!(from_dir, [alpha.clone(), beta.clone()]);
+
+        let pattern = to_string(&dir.path().join("*.whl"));
+        assert_eq!(
+            expand_wheel_paths(&[pattern]).unwrap(),
+            [alpha.clone(), beta.clone()]
+        );
+
+        // Only wheel files are picked up, not notes.txt or the nested directory
+        let pattern = to_string(&dir.path().join("*"));
+        assert_eq!(
+            expand_wheel_paths(&[pattern]).unwrap(),
+            [alpha.clone(), beta.clone()]
+        );
+
+        // Duplicates are dropped, keeping the first occurrence
+        let inputs = [to_string(&beta), to_string(dir.path())];
+        assert_eq!(expand_wheel_paths(&inputs).unwrap(), [beta, alpha]);
+    }
+
+    #[test]
+    fn rejects_empty_expansions() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let err = expand_wheel_paths(&[to_string(dir.path())]).unwrap_err();
+        assert!(err.to_string().starts_with("No wheels found in directory"));
+
+        let pattern = to_string(&dir.path().join("*.whl"));
+        let err = expand_wheel_paths(&[pattern]).unwrap_err();
+        assert!(err.to_string().starts_with("No wheels match"));
+
+        // Plain paths are passed through, to fail when processed
+        let missing = to_string(&dir.path().join("missing-1.0-py3-none-any.whl"));
+        assert_eq!(
+            expand_wheel_paths(std::slice::from_ref(&missing)).unwrap(),
+            [PathBuf::from(missing)]
+        );
+    }
+
+    #[test]
+    fn rejects_colliding_output_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        for subdir in ["a", "b"] {
+            fs::create_dir(dir.path().join(subdir)).unwrap();
+        }
+        let wheel_paths = [
+            write_wheel(&dir.path().join("a"), "demo"),
+            write_wheel(&dir.path().join("b"), "demo"),
+        ];
+        let output_dir = dir.path().join("out");
+
+        let err = process_wheels(
+            &wheel_paths,
+            Some(&output_dir),
+            &CapturedEnv::default(),
+            &InjectOptions::default(),
+            2,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "More than one wheel would be written to {}",
+                output_dir.join("demo-1.0-py3-none-any.whl").display()
+            )
+        );
+        assert!(!output_dir.exists());
+    }
+
+    #[test]
+    fn processes_remaining_wheels_after_a_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let broken = dir.path().join("broken-1.0-py3-none-any.whl");
+        fs::write(&broken, "not a zip archive").unwrap();
+        let wheel_paths = [
+            write_wheel(dir.path(), "alpha"),
+            broken,
+            write_wheel(dir.path(), "beta"),
+            write_wheel(dir.path(), "gamma"),
+        ];
+        let output_dir = dir.path().join("out");
+        let mut options = InjectOptions::default();
+        options.git.source_dir = Some(dir.path().to_path_buf());
+
+        let results = process_wheels(
+            &wheel_paths,
+            Some(&output_dir),
+            &CapturedEnv::default(),
+            &options,
+            2,
+        )
+        .unwrap();
+
+        let wheels: Vec<_> = results.iter().map(|r| r.wheel_path.clone()).collect();
+        assert_eq!(wheels, wheel_paths);
+        let failed: Vec<_> = results.iter().map(|r| r.result.is_err()).collect();
+        assert_eq!(failed, [false, true, false, false]);
+        for result in results.iter().filter(|r| r.result.is_ok()) {
+            assert_eq!(
+                result.output_path,
+                output_dir.join(result.wheel_path.file_name().unwrap())
+            );
+            assert!(result.output_path.is_file());
+        }
+    }
+}
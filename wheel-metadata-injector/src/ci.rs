@@ -0,0 +1,568 @@
+use std::env;
+
+use common::{ActionsInfo, AutomationInfo, GitSource, RepositoryInfo};
+
+use crate::git::{is_commit_hash, normalize_remote_url};
+
+/// Source of environment variables for CI detection, so providers can be checked
+/// against a fixed set of variables instead of the process environment
+pub trait EnvLookup {
+    /// The value of `name`, `None` if it is unset or empty
+    fn var(&self, name: &str) -> Option<String>;
+}
+
+impl<F> EnvLookup for F
+where
+    F: Fn(&str) -> Option<String>,
+{
+    fn var(&self, name: &str) -> Option<String> {
+        self(name).filter(|value| !value.is_empty())
+    }
+}
+
+/// The environment of the current process
+pub fn process_env(name: &str) -> Option<String> {
+    env::var(name).ok()
+}
+
+/// A CI system the injector can recognize and describe
+pub trait CiProvider: Sync {
+    /// Name recorded as the `provider` of the automation info
+    fn name(&self) -> &'static str;
+
+    /// Whether the build is running on this CI system
+    fn detect(&self, env: &dyn EnvLookup) -> bool;
+
+    /// Describe the pipeline running the build. `provider` is filled in by
+    /// [`detect_pipeline`].
+    fn info(&self, env: &dyn EnvLookup) -> AutomationInfo;
+
+    /// Permalink to the job or attempt running the build, the pipeline URL if the
+    /// system offers nothing more specific
+    fn run_url(&self, env: &dyn EnvLookup) -> Option<String> {
+        self.info(env).pipeline_url
+    }
+
+    /// The repository and commit being built, for builds without a git checkout
+    fn repository(&self, _env: &dyn EnvLookup) -> Option<RepositoryInfo> {
+        None
+    }
+}
+
+/// The CI systems that are recognized, checked in order
+pub const CI_PROVIDERS: &[&dyn CiProvider] = &[
+    &GitHubActions,
+    &GitLabCi,
+    &Buildkite,
+    &AzurePipelines,
+    &CircleCi,
+    &Tekton,
+    &Jenkins,
+];
+
+/// Describe the pipeline of the first provider in [`CI_PROVIDERS`] detected in
+/// `env`, `None` outside of CI
+pub fn detect_pipeline(env: &dyn EnvLookup) -> Option<AutomationInfo> {
+    let provider = CI_PROVIDERS.iter().find(|provider| provider.detect(env))?;
+
+    Some(AutomationInfo {
+        provider: Some(provider.name().to_string()),
+        run_url: provider.run_url(env),
+        ..provider.info(env)
+    })
+}
+
+/// Describe the repository being built from the variables of the first provider
+/// in [`CI_PROVIDERS`] detected in `env`
+pub fn detect_repository(env: &dyn EnvLookup) -> Option<RepositoryInfo> {
+    CI_PROVIDERS
+        .iter()
+        .find(|provider| provider.detect(env))?
+        .repository(env)
+}
+
+/// Repository info from CI variables, `None` unless `commit` is a commit hash
+fn repository(
+    commit: Option<String>,
+    url: Option<String>,
+    branch: Option<String>,
+    tag: Option<String>,
+) -> Option<RepositoryInfo> {
+    let commit = commit.filter(|commit| is_commit_hash(commit))?;
+
+    Some(RepositoryInfo {
+        url: url.map(|url| normalize_remote_url(&url)),
+        commit,
+        source: Some(GitSource::Ci),
+        branch,
+        tags: tag.into_iter().collect(),
+        ..Default::default()
+    })
+}
+
+/// Split a full ref name into a branch or a tag
+fn split_ref(git_ref: Option<String>) -> (Option<String>, Option<String>) {
+    match git_ref {
+        Some(git_ref) => match git_ref.strip_prefix("refs/tags/") {
+            Some(tag) => (None, Some(tag.to_string())),
+            None => (
+                git_ref
+                    .strip_prefix("refs/heads/")
+                    .map(|branch| branch.to_string()),
+                None,
+            ),
+        },
+        None => (None, None),
+    }
+}
+
+/// Parse an attempt number, `offset` converting a zero-based retry count
+fn attempt(value: Option<String>, offset: u32) -> Option<u32> {
+    value?.trim().parse::<u32>().ok()?.checked_add(offset)
+}
+
+pub struct GitHubActions;
+
+impl CiProvider for GitHubActions {
+    fn name(&self) -> &'static str {
+        "github-actions"
+    }
+
+    fn detect(&self, env: &dyn EnvLookup) -> bool {
+        env.var("GITHUB_ACTIONS").is_some()
+    }
+
+    fn info(&self, env: &dyn EnvLookup) -> AutomationInfo {
+        let pipeline_url = match (env.var("GITHUB_REPOSITORY"), env.var("GITHUB_RUN_ID")) {
+            (Some(repository), Some(run_id)) => Some(format!(
+                "{}/{}/actions/runs/{}",
+                env.var("GITHUB_SERVER_URL")
+                    .as_deref()
+                    .unwrap_or("https://github.com")
+                    .trim_end_matches('/'),
+                repository,
+                run_id
+            )),
+            _ => None,
+        };
+
+        AutomationInfo {
+            pipeline_url,
+            job_id: env.var("GITHUB_JOB"),
+            attempt: attempt(env.var("GITHUB_RUN_ATTEMPT"), 0),
+            actor: env
+                .var("GITHUB_TRIGGERING_ACTOR")
+                .or_else(|| env.var("GITHUB_ACTOR")),
+            git_ref: env.var("GITHUB_REF"),
+            actions_info: Some(ActionsInfo {
+                run_id: env.var("GITHUB_RUN_ID"),
+                workflow_name: env.var("GITHUB_WORKFLOW"),
+                workflow_sha: env.var("GITHUB_WORKFLOW_SHA"),
+                job_name: env.var("GITHUB_JOB"),
+                runner_name: env.var("RUNNER_NAME"),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn repository(&self, env: &dyn EnvLookup) -> Option<RepositoryInfo> {
+        let url = env.var("GITHUB_REPOSITORY").map(|repository| {
+            format!(
+                "{}/{}",
+                env.var("GITHUB_SERVER_URL")
+                    .as_deref()
+                    .unwrap_or("https://github.com")
+                    .trim_end_matches('/'),
+                repository
+            )
+        });
+        let (branch, tag) = split_ref(env.var("GITHUB_REF"));
+
+        repository(env.var("GITHUB_SHA"), url, branch, tag)
+    }
+
+    fn run_url(&self, env: &dyn EnvLookup) -> Option<String> {
+        let pipeline_url = self.info(env).pipeline_url?;
+        match env.var("GITHUB_RUN_ATTEMPT") {
+            Some(attempt) => Some(format!("{}/attempts/{}", pipeline_url, attempt)),
+            None => Some(pipeline_url),
+        }
+    }
+}
+
+pub struct GitLabCi;
+
+impl CiProvider for GitLabCi {
+    fn name(&self) -> &'static str {
+        "gitlab-ci"
+    }
+
+    fn detect(&self, env: &dyn EnvLookup) -> bool {
+        env.var("GITLAB_CI").is_some()
+    }
+
+    fn info(&self, env: &dyn EnvLookup) -> AutomationInfo {
+        AutomationInfo {
+            pipeline_url: env.var("CI_PIPELINE_URL"),
+            job_id: env.var("CI_JOB_ID"),
+            actor: env.var("GITLAB_USER_LOGIN"),
+            git_ref: env.var("CI_COMMIT_REF_NAME"),
+            ..Default::default()
+        }
+    }
+
+    fn repository(&self, env: &dyn EnvLookup) -> Option<RepositoryInfo> {
+        repository(
+            env.var("CI_COMMIT_SHA"),
+            env.var("CI_PROJECT_URL"),
+            env.var("CI_COMMIT_BRANCH"),
+            env.var("CI_COMMIT_TAG"),
+        )
+    }
+
+    fn run_url(&self, env: &dyn EnvLookup) -> Option<String> {
+        let job_url = match (env.var("CI_PROJECT_URL"), env.var("CI_JOB_ID")) {
+            (Some(project_url), Some(job_id)) => Some(format!(
+                "{}/-/jobs/{}",
+                project_url.trim_end_matches('/'),
+                job_id
+            )),
+            _ => None,
+        };
+
+        env.var("CI_JOB_URL")
+            .or(job_url)
+            .or_else(|| env.var("CI_PIPELINE_URL"))
+    }
+}
+
+pub struct Buildkite;
+
+impl CiProvider for Buildkite {
+    fn name(&self) -> &'static str {
+        "buildkite"
+    }
+
+    fn detect(&self, env: &dyn EnvLookup) -> bool {
+        env.var("BUILDKITE").is_some()
+    }
+
+    fn info(&self, env: &dyn EnvLookup) -> AutomationInfo {
+        AutomationInfo {
+            pipeline_url: env.var("BUILDKITE_BUILD_URL"),
+            job_id: env.var("BUILDKITE_JOB_ID"),
+            attempt: attempt(env.var("BUILDKITE_RETRY_COUNT"), 1),
+            actor: env.var("BUILDKITE_BUILD_CREATOR"),
+            git_ref: env
+                .var("BUILDKITE_TAG")
+                .or_else(|| env.var("BUILDKITE_BRANCH")),
+            ..Default::default()
+        }
+    }
+
+    fn repository(&self, env: &dyn EnvLookup) -> Option<RepositoryInfo> {
+        let tag = env.var("BUILDKITE_TAG");
+        let branch = env.var("BUILDKITE_BRANCH").filter(|_| tag.is_none());
+
+        repository(
+            env.var("BUILDKITE_COMMIT"),
+            env.var("BUILDKITE_REPO"),
+            branch,
+            tag,
+        )
+    }
+
+    fn run_url(&self, env: &dyn EnvLookup) -> Option<String> {
+        let build_url = env.var("BUILDKITE_BUILD_URL")?;
+        match env.var("BUILDKITE_JOB_ID") {
+            Some(job_id) => Some(format!("{}#{}", build_url, job_id)),
+            None => Some(build_url),
+        }
+    }
+}
+
+pub struct AzurePipelines;
+
+impl CiProvider for AzurePipelines {
+    fn name(&self) -> &'static str {
+        "azure-pipelines"
+    }
+
+    fn detect(&self, env: &dyn EnvLookup) -> bool {
+        env.var("TF_BUILD").is_some()
+    }
+
+    fn info(&self, env: &dyn EnvLookup) -> AutomationInfo {
+        let pipeline_url = match (
+            env.var("SYSTEM_COLLECTIONURI"),
+            env.var("SYSTEM_TEAMPROJECT"),
+            env.var("BUILD_BUILDID"),
+        ) {
+            (Some(collection), Some(project), Some(build_id)) => Some(format!(
+                "{}/{}/_build/results?buildId={}",
+                collection.trim_end_matches('/'),
+                project,
+                build_id
+            )),
+            _ => None,
+        };
+
+        AutomationInfo {
+            pipeline_url,
+            job_id: env.var("SYSTEM_JOBID"),
+            attempt: attempt(env.var("SYSTEM_JOBATTEMPT"), 0),
+            actor: env.var("BUILD_REQUESTEDFOR"),
+            git_ref: env.var("BUILD_SOURCEBRANCH"),
+            ..Default::default()
+        }
+    }
+
+    fn repository(&self, env: &dyn EnvLookup) -> Option<RepositoryInfo> {
+        let (branch, tag) = split_ref(env.var("BUILD_SOURCEBRANCH"));
+
+        repository(
+            env.var("BUILD_SOURCEVERSION"),
+            env.var("BUILD_REPOSITORY_URI"),
+            branch,
+            tag,
+        )
+    }
+
+    fn run_url(&self, env: &dyn EnvLookup) -> Option<String> {
+        let pipeline_url = self.info(env).pipeline_url?;
+        match env.var("SYSTEM_JOBID") {
+            Some(job_id) => Some(format!("{}&view=logs&j={}", pipeline_url, job_id)),
+            None => Some(pipeline_url),
+        }
+    }
+}
+
+pub struct CircleCi;
+
+impl CiProvider for CircleCi {
+    fn name(&self) -> &'static str {
+        "circleci"
+    }
+
+    fn detect(&self, env: &dyn EnvLookup) -> bool {
+        env.var("CIRCLECI").is_some()
+    }
+
+    fn info(&self, env: &dyn EnvLookup) -> AutomationInfo {
+        AutomationInfo {
+            pipeline_url: env.var("CIRCLE_BUILD_URL"),
+            job_id: env.var("CIRCLE_WORKFLOW_JOB_ID"),
+            actor: env.var("CIRCLE_USERNAME"),
+            git_ref: env.var("CIRCLE_TAG").or_else(|| env.var("CIRCLE_BRANCH")),
+            ..Default::default()
+        }
+    }
+
+    fn repository(&self, env: &dyn EnvLookup) -> Option<RepositoryInfo> {
+        repository(
+            env.var("CIRCLE_SHA1"),
+            env.var("CIRCLE_REPOSITORY_URL"),
+            env.var("CIRCLE_BRANCH"),
+            env.var("CIRCLE_TAG"),
+        )
+    }
+}
+
+/// Tekton does not set any variables itself, so tasks are expected to expose the
+/// run context, e.g. `TEKTON_PIPELINE_RUN` from `$(context.pipelineRun.name)`
+pub struct Tekton;
+
+impl CiProvider for Tekton {
+    fn name(&self) -> &'static str {
+        "tekton"
+    }
+
+    fn detect(&self, env: &dyn EnvLookup) -> bool {
+        env.var("TEKTON_PIPELINE_RUN").is_some() || env.var("TEKTON_TASK_RUN").is_some()
+    }
+
+    fn info(&self, env: &dyn EnvLookup) -> AutomationInfo {
+        AutomationInfo {
+            pipeline_url: env.var("TEKTON_PIPELINE_RUN_URL"),
+            job_id: env
+                .var("TEKTON_TASK_RUN")
+                .or_else(|| env.var("TEKTON_PIPELINE_RUN")),
+            attempt: attempt(env.var("TEKTON_TASK_RETRY_COUNT"), 1),
+            actor: env.var("TEKTON_ACTOR"),
+            git_ref: env.var("TEKTON_GIT_REF"),
+            ..Default::default()
+        }
+    }
+}
+
+/// Checked last, as `JENKINS_URL` may leak into builds running elsewhere
+pub struct Jenkins;
+
+impl CiProvider for Jenkins {
+    fn name(&self) -> &'static str {
+        "jenkins"
+    }
+
+    fn detect(&self, env: &dyn EnvLookup) -> bool {
+        env.var("JENKINS_URL").is_some() && env.var("BUILD_TAG").is_some()
+    }
+
+    fn info(&self, env: &dyn EnvLookup) -> AutomationInfo {
+        AutomationInfo {
+            pipeline_url: env.var("BUILD_URL"),
+            job_id: env.var("BUILD_TAG"),
+            actor: env.var("BUILD_USER_ID"),
+            git_ref: env.var("BRANCH_NAME").or_else(|| env.var("GIT_BRANCH")),
+            ..Default::default()
+        }
+    }
+
+    fn repository(&self, env: &dyn EnvLookup) -> Option<RepositoryInfo> {
+        repository(
+            env.var("GIT_COMMIT"),
+            env.var("GIT_URL"),
+            env.var("BRANCH_NAME"),
+            env.var("TAG_NAME"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: Vec<(String, String)> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| var == name)
+                .map(|(_, value)| value.clone())
+        }
+    }
+
+    #[test]
+    fn detects_nothing_outside_ci() {
+        assert!(detect_pipeline(&vars(&[("HOME", "/root")])).is_none());
+        assert!(detect_pipeline(&vars(&[("GITHUB_ACTIONS", "")])).is_none());
+    }
+
+    #[test]
+    fn describes_github_actions() {
+        let info = detect_pipeline(&vars(&[
+            ("GITHUB_ACTIONS", "true"),
+            ("GITHUB_REPOSITORY", "neuralmagic/nm-cicd"),
+            ("GITHUB_RUN_ID", "14842309063"),
+            ("GITHUB_RUN_ATTEMPT", "2"),
+            ("GITHUB_JOB", "build"),
+            ("GITHUB_ACTOR", "octocat"),
+            ("GITHUB_REF", "refs/tags/v1.0"),
+        ]))
+        .unwrap();
+
+        assert_eq!(info.provider.as_deref(), Some("github-actions"));
+        assert_eq!(
+            info.run_url.as_deref(),
+            Some("https://github.com/neuralmagic/nm-cicd/actions/runs/14842309063/attempts/2")
+        );
+        assert_eq!(info.job_id.as_deref(), Some("build"));
+        assert_eq!(info.attempt, Some(2));
+        assert_eq!(info.actor.as_deref(), Some("octocat"));
+        assert_eq!(info.git_ref.as_deref(), Some("refs/tags/v1.0"));
+        assert_eq!(
+            info.actions_info.unwrap().run_id.as_deref(),
+            Some("14842309063")
+        );
+    }
+
+    #[test]
+    fn describes_repository_without_checkout() {
+        let info = detect_repository(&vars(&[
+            ("GITHUB_ACTIONS", "true"),
+            ("GITHUB_REPOSITORY", "neuralmagic/nm-cicd"),
+            ("GITHUB_SHA", "661d4dc60faad00bf7e74cef911ed35a25806ad2"),
+            ("GITHUB_REF", "refs/tags/v1.0"),
+        ]))
+        .unwrap();
+        assert_eq!(info.commit, "661d4dc60faad00bf7e74cef911ed35a25806ad2");
+        assert_eq!(info.source, Some(GitSource::Ci));
+        assert_eq!(
+            info.url.as_deref(),
+            Some("https://github.com/neuralmagic/nm-cicd")
+        );
+        assert_eq!(info.tags, ["v1.0"]);
+        assert!(info.branch.is_none());
+
+        let info = detect_repository(&vars(&[
+            ("GITLAB_CI", "true"),
+            ("CI_COMMIT_SHA", "661d4dc60faad00bf7e74cef911ed35a25806ad2"),
+            ("CI_PROJECT_URL", "https://gitlab.com/org/wheels"),
+            ("CI_COMMIT_BRANCH", "main"),
+        ]))
+        .unwrap();
+        assert_eq!(info.branch.as_deref(), Some("main"));
+
+        // Buildkite builds triggered without a commit use HEAD
+        assert!(
+            detect_repository(&vars(&[
+                ("BUILDKITE", "true"),
+                ("BUILDKITE_COMMIT", "HEAD")
+            ]))
+            .is_none()
+        );
+        assert!(detect_repository(&vars(&[("TEKTON_PIPELINE_RUN", "build-x1")])).is_none());
+    }
+
+    #[test]
+    fn describes_other_providers() {
+        let info = detect_pipeline(&vars(&[
+            ("BUILDKITE", "true"),
+            (
+                "BUILDKITE_BUILD_URL",
+                "https://buildkite.com/org/wheels/builds/7",
+            ),
+            ("BUILDKITE_RETRY_COUNT", "0"),
+            ("BUILDKITE_BRANCH", "main"),
+        ]))
+        .unwrap();
+        assert_eq!(info.provider.as_deref(), Some("buildkite"));
+        assert_eq!(info.attempt, Some(1));
+        assert_eq!(info.git_ref.as_deref(), Some("main"));
+        assert_eq!(
+            info.run_url.as_deref(),
+            Some("https://buildkite.com/org/wheels/builds/7")
+        );
+        assert!(info.actions_info.is_none());
+
+        let info = detect_pipeline(&vars(&[
+            ("TF_BUILD", "True"),
+            ("SYSTEM_COLLECTIONURI", "https://dev.azure.com/org/"),
+            ("SYSTEM_TEAMPROJECT", "wheels"),
+            ("BUILD_BUILDID", "42"),
+            ("SYSTEM_JOBID", "a1b2"),
+        ]))
+        .unwrap();
+        assert_eq!(info.provider.as_deref(), Some("azure-pipelines"));
+        assert_eq!(
+            info.pipeline_url.as_deref(),
+            Some("https://dev.azure.com/org/wheels/_build/results?buildId=42")
+        );
+        assert_eq!(
+            info.run_url.as_deref(),
+            Some("https://dev.azure.com/org/wheels/_build/results?buildId=42&view=logs&j=a1b2")
+        );
+
+        // GitLab runners started by Jenkins are still GitLab jobs
+        let info = detect_pipeline(&vars(&[
+            ("JENKINS_URL", "https://jenkins.example.com/"),
+            ("BUILD_TAG", "jenkins-wheels-3"),
+            ("GITLAB_CI", "true"),
+            ("CI_JOB_ID", "99"),
+        ]))
+        .unwrap();
+        assert_eq!(info.provider.as_deref(), Some("gitlab-ci"));
+        assert_eq!(info.job_id.as_deref(), Some("99"));
+    }
+}
@@ -0,0 +1,228 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Context;
+use common::EnvSource;
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    CapturedEnv, ExistingMetadata, InjectOptions, OutputFormat, RecordVerification, RedactionMode,
+    collect_env_vars_from_file, select_env_vars,
+};
+
+pub const PYPROJECT_FILENAME: &str = "pyproject.toml";
+
+/// Settings from the `[tool.wheel-metadata-injector]` table of a project's
+/// pyproject.toml. Command line flags and explicit arguments take precedence.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Variables to collect, one entry per line of an env var list file: names,
+    /// patterns, negations and transforms.
+    pub env_vars: Option<Vec<String>>,
+    /// An env var list file, relative to the pyproject.toml.
+    pub env_file: Option<PathBuf>,
+    #[serde(default, deserialize_with = "parse_optional")]
+    pub redact: Option<RedactionMode>,
+    /// Variables (or `*` patterns) to record verbatim even if they look like secrets.
+    #[serde(default)]
+    pub allow_secrets: Vec<String>,
+    pub reproducible: Option<bool>,
+    #[serde(default, deserialize_with = "parse_optional")]
+    pub existing: Option<ExistingMetadata>,
+    #[serde(default, deserialize_with = "parse_optional")]
+    pub verify_record: Option<RecordVerification>,
+    #[serde(default, deserialize_with = "parse_optional")]
+    pub format: Option<OutputFormat>,
+    /// Remotes whose URL is recorded, in order of preference.
+    pub git_remotes: Option<Vec<String>>,
+    /// Also record the URLs of all remotes.
+    pub all_remotes: Option<bool>,
+    /// Static fields recorded in the `extra` table of the build environment file.
+    #[serde(default)]
+    pub extra: toml::Table,
+}
+
+#[derive(Deserialize)]
+struct PyProject {
+    #[serde(default)]
+    tool: Tool,
+}
+
+#[derive(Default, Deserialize)]
+struct Tool {
+    #[serde(rename = "wheel-metadata-injector")]
+    wheel_metadata_injector: Option<ProjectConfig>,
+}
+
+impl ProjectConfig {
+    /// Find the pyproject.toml of the project containing `dir`, the nearest one in
+    /// `dir` or its ancestors, and load its configuration. Returns the path of the
+    /// pyproject.toml along with the configuration, `None` if there is no
+    /// pyproject.toml or it has no `[tool.wheel-metadata-injector]` table.
+    pub fn discover(dir: &Path) -> anyhow::Result<Option<(PathBuf, Self)>> {
+        for dir in dir.ancestors() {
+            let path = dir.join(PYPROJECT_FILENAME);
+            if path.is_file() {
+                return Ok(Self::load(&path)?.map(|config| (path, config)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Load the configuration from a pyproject.toml, `None` if it has no
+    /// `[tool.wheel-metadata-injector]` table
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let pyproject: PyProject = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        let Some(mut config) = pyproject.tool.wheel_metadata_injector else {
+            return Ok(None);
+        };
+        if config.env_vars.is_some() && config.env_file.is_some() {
+            anyhow::bail!(
+                "{}: set either env-vars or env-file in [tool.wheel-metadata-injector], not both",
+                path.display()
+            );
+        }
+        if let (Some(env_file), Some(project_dir)) = (&config.env_file, path.parent()) {
+            config.env_file = Some(project_dir.join(env_file));
+        }
+
+        Ok(Some(config))
+    }
+
+    /// Override the fields of `options` that are set in the configuration. Secrets
+    /// allowed by the configuration are added to those already allowed.
+    pub fn apply(&self, options: &mut InjectOptions) {
+        if let Some(reproducible) = self.reproducible {
+            options.reproducible = reproducible;
+        }
+        if let Some(existing) = self.existing {
+            options.existing = existing;
+        }
+        if let Some(verify_record) = self.verify_record {
+            options.verify_record = verify_record;
+        }
+        if let Some(redact) = self.redact {
+            options.redaction.mode = redact;
+        }
+        options
+            .redaction
+            .allow
+            .extend(self.allow_secrets.iter().cloned());
+        if let Some(format) = self.format {
+            options.format = format;
+        }
+        options.extra.extend(self.extra.clone());
+        if let Some(git_remotes) = &self.git_remotes {
+            options.git.remotes = git_remotes.clone();
+        }
+        if let Some(all_remotes) = self.all_remotes {
+            options.git.all_remotes = all_remotes;
+        }
+    }
+
+    /// Collect the variables configured with `env-vars` or `env-file`, `None` if
+    /// neither is set
+    pub fn collect_env_vars(&self) -> anyhow::Result<Option<CapturedEnv>> {
+        if let Some(env_vars) = &self.env_vars {
+            let env = select_env_vars(env_vars, EnvSource::Config)
+                .context("Invalid env-vars in [tool.wheel-metadata-injector]")?;
+            return Ok(Some(env));
+        }
+        if let Some(env_file) = &self.env_file {
+            let mut env = collect_env_vars_from_file(&env_file.to_string_lossy())?;
+            env.source = Some(EnvSource::Config);
+            return Ok(Some(env));
+        }
+
+        Ok(None)
+    }
+}
+
+fn parse_optional<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_tool_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PYPROJECT_FILENAME);
+        fs::write(
+            &path,
+            r#"
+[project]
+name = "demo"
+
+[tool.wheel-metadata-injector]
+env-file = "ci/env_vars.txt"
+redact = "refuse"
+allow-secrets = ["PYTORCH_TOKEN_*"]
+format = "json"
+
+[tool.wheel-metadata-injector.extra]
+team = "kernels"
+"#,
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+
+        let (found, config) = ProjectConfig::discover(&dir.path().join("src"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(found, path);
+        assert_eq!(config.env_file, Some(dir.path().join("ci/env_vars.txt")));
+
+        let mut options = InjectOptions::default();
+        config.apply(&mut options);
+        assert_eq!(options.redaction.mode, RedactionMode::Refuse);
+        assert_eq!(options.redaction.allow, ["PYTORCH_TOKEN_*"]);
+        assert_eq!(options.format, OutputFormat::Json);
+        assert_eq!(options.existing, ExistingMetadata::Replace);
+        assert_eq!(options.extra["team"].as_str(), Some("kernels"));
+
+        fs::create_dir(dir.path().join("ci")).unwrap();
+        fs::write(
+            dir.path().join("ci/env_vars.txt"),
+            "WHEEL_METADATA_INJECTOR_UNSET_VAR\n",
+        )
+        .unwrap();
+        let env = config.collect_env_vars().unwrap().unwrap();
+        assert_eq!(env.source, Some(EnvSource::Config));
+        assert_eq!(env.missing, ["WHEEL_METADATA_INJECTOR_UNSET_VAR"]);
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PYPROJECT_FILENAME);
+
+        fs::write(
+            &path,
+            "[tool.wheel-metadata-injector]\nredact = \"maybe\"\n",
+        )
+        .unwrap();
+        assert!(ProjectConfig::load(&path).is_err());
+
+        fs::write(&path, "[tool.wheel-metadata-injector]\nenv-var = [\"A\"]\n").unwrap();
+        assert!(ProjectConfig::load(&path).is_err());
+
+        fs::write(&path, "[project]\nname = \"demo\"\n").unwrap();
+        assert!(ProjectConfig::load(&path).unwrap().is_none());
+    }
+}
@@ -0,0 +1,272 @@
+use std::io::{Read, Seek};
+
+use zip::ZipArchive;
+
+/// Entries smaller than this are not subject to the compression ratio limit, tiny
+/// files of repetitive text routinely compress far better than real payloads
+const RATIO_CHECK_MIN_SIZE: u64 = 1024 * 1024;
+
+/// Limits applied to wheels before they are read or extracted, so that archives
+/// from untrusted builders cannot escape the output directory or exhaust the disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveLimits {
+    /// Maximum number of entries in the archive.
+    pub max_entries: usize,
+    /// Maximum total uncompressed size of all entries, in bytes.
+    pub max_total_size: u64,
+    /// Maximum ratio between an entry's uncompressed and compressed size.
+    pub max_compression_ratio: u64,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        ArchiveLimits {
+            max_entries: 100_000,
+            // Large enough for CUDA wheels bundling several toolkits' worth of kernels
+            max_total_size: 32 * 1024 * 1024 * 1024,
+            max_compression_ratio: 200,
+        }
+    }
+}
+
+/// Check every entry of `archive` against `limits`, using the sizes declared in the
+/// central directory. Entry names must stay inside the archive root (no absolute
+/// paths or `..` escapes) and symlink entries are rejected outright.
+pub fn check_archive<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    limits: &ArchiveLimits,
+) -> anyhow::Result<()> {
+    if archive.len() > limits.max_entries {
+        anyhow::bail!(
+            "Wheel has {} entries, more than the limit of {}",
+            archive.len(),
+            limits.max_entries
+        );
+    }
+
+    let mut total_size: u64 = 0;
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+
+        if file.enclosed_name().is_none() {
+            anyhow::bail!("Wheel entry '{}' has an unsafe path", file.name());
+        }
+
+        if file.is_symlink() {
+            anyhow::bail!("Wheel entry '{}' is a symlink", file.name());
+        }
+
+        total_size = total_size.saturating_add(file.size());
+        if total_size > limits.max_total_size {
+            anyhow::bail!(
+                "Wheel contents exceed the uncompressed size limit of {} bytes",
+                limits.max_total_size
+            );
+        }
+
+        if file.size() >= RATIO_CHECK_MIN_SIZE
+            && file.size() / file.compressed_size().max(1) > limits.max_compression_ratio
+        {
+            anyhow::bail!(
+                "Wheel entry '{}' has a compression ratio above the limit of {}:1",
+                file.name(),
+                limits.max_compression_ratio
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    use common::{Wheel, read_entry_to_string};
+
+    use super::*;
+
+    fn archive(build: impl FnOnce(&mut ZipWriter<Cursor<Vec<u8>>>)) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        build(&mut writer);
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    fn add_file(writer: &mut ZipWriter<Cursor<Vec<u8>>>, name: &str, content: &[u8]) {
+        writer
+            .start_file(name, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(content).unwrap();
+    }
+
+    fn check(archive: &mut ZipArchive<Cursor<Vec<u8>>>) -> String {
+        check_archive(archive, &ArchiveLimits::default())
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn accepts_regular_wheel() {
+        let mut archive = archive(|writer| {
+            add_file(writer, "demo/__init__.py", b"");
+            add_file(writer, "demo-1.0.dist-info/RECORD", b"");
+        });
+
+        check_archive(&mut archive, &ArchiveLimits::default()).unwrap();
+    }
+
+    #[test]
+    fn rejects_parent_dir_escape() {
+        let mut archive = archive(|writer| add_file(writer, "demo/../../evil.py", b""));
+
+        assert_eq!(
+            check(&mut archive),
+            "Wheel entry 'demo/../../evil.py' has an unsafe path"
+        );
+    }
+
+    #[test]
+    fn rejects_absolute_path() {
+        let mut archive = archive(|writer| add_file(writer, "/etc/evil.py", b""));
+
+        assert_eq!(
+            check(&mut archive),
+            "Wheel entry '/etc/evil.py' has an unsafe path"
+        );
+    }
+
+    #[test]
+    fn rejects_symlink() {
+        let mut archive = archive(|writer| {
+            writer
+                .add_symlink("demo/link", "/etc/passwd", SimpleFileOptions::default())
+                .unwrap();
+        });
+
+        assert_eq!(check(&mut archive), "Wheel entry 'demo/link' is a symlink");
+    }
+
+    #[test]
+    fn rejects_too_many_entries() {
+        let mut archive = archive(|writer| {
+            add_file(writer, "a.py", b"");
+            add_file(writer, "b.py", b"");
+        });
+        let limits = ArchiveLimits {
+            max_entries: 1,
+            ..ArchiveLimits::default()
+        };
+
+        let err = check_archive(&mut archive, &limits).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Wheel has 2 entries, more than the limit of 1"
+        );
+    }
+
+    #[test]
+    fn rejects_total_size_above_limit() {
+        let mut archive = archive(|writer| {
+            add_file(writer, "a.py", &[b'a'; 600]);
+            add_file(writer, "b.py", &[b'b'; 600]);
+        });
+        let limits = ArchiveLimits {
+            max_total_size: 1000,
+            ..ArchiveLimits::default()
+        };
+
+        let err = check_archive(&mut archive, &limits).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Wheel contents exceed the uncompressed size limit of 1000 bytes"
+        );
+    }
+
+    #[test]
+    fn rejects_compression_ratio_above_limit() {
+        let zeros = vec![0; 2 * RATIO_CHECK_MIN_SIZE as usize];
+        let mut archive = archive(|writer| add_file(writer, "demo/zeros.bin", &zeros));
+
+        assert_eq!(
+            check(&mut archive),
+            "Wheel entry 'demo/zeros.bin' has a compression ratio above the limit of 200:1"
+        );
+    }
+
+    #[test]
+    fn ignores_compression_ratio_of_small_entries() {
+        let zeros = vec![0; RATIO_CHECK_MIN_SIZE as usize - 1];
+        let mut archive = archive(|writer| add_file(writer, "demo/zeros.bin", &zeros));
+
+        check_archive(&mut archive, &ArchiveLimits::default()).unwrap();
+    }
+
+    #[test]
+    fn reads_entries_up_to_declared_size() {
+        let mut archive = archive(|writer| add_file(writer, "demo/data.txt", b"hello"));
+
+        let content = read_entry_to_string(&mut archive.by_index(0).unwrap()).unwrap();
+        assert_eq!(content, "hello");
+    }
+
+    /// Store `files` uncompressed, with `understated` declaring an uncompressed size
+    /// of 5 bytes in both its local and central headers
+    fn understated_archive(files: &[(&str, &[u8])], understated: &str) -> Cursor<Vec<u8>> {
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        let mut bytes = writer.finish().unwrap().into_inner();
+
+        // The name follows the fixed 30 byte local header and 46 byte central header
+        let name = understated.as_bytes();
+        let positions: Vec<_> = bytes
+            .windows(name.len())
+            .enumerate()
+            .filter(|(_, window)| *window == name)
+            .map(|(i, _)| i)
+            .collect();
+        let (local, central) = (positions[0] - 30, positions[1] - 46);
+        bytes[local + 22..local + 26].copy_from_slice(&5u32.to_le_bytes());
+        bytes[central + 24..central + 28].copy_from_slice(&5u32.to_le_bytes());
+
+        Cursor::new(bytes)
+    }
+
+    #[test]
+    fn rejects_entry_larger_than_declared() {
+        let bytes = understated_archive(&[("demo/data.txt", b"hello world")], "demo/data.txt");
+
+        let mut archive = ZipArchive::new(bytes).unwrap();
+        let err = read_entry_to_string(&mut archive.by_index(0).unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Wheel entry 'demo/data.txt' decompresses to more than its declared size of 5 bytes"
+        );
+    }
+
+    #[test]
+    fn rejects_build_env_larger_than_declared() {
+        let bytes = understated_archive(
+            &[
+                ("demo-1.0.dist-info/WHEEL.metadata", b"[env]\nA = \"1\"\n"),
+                ("demo-1.0.dist-info/RECORD", b""),
+            ],
+            "demo-1.0.dist-info/WHEEL.metadata",
+        );
+
+        let mut wheel = Wheel::new(bytes, None).unwrap();
+        let err = wheel.build_env().unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "Failed to read file from archive: demo-1.0.dist-info/WHEEL.metadata: \
+             Wheel entry 'demo-1.0.dist-info/WHEEL.metadata' decompresses to more than its \
+             declared size of 5 bytes"
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::thread;
+
+use clap::Parser;
+
+use wheel_metadata_injector::{
+    ArchiveLimits, CapturedEnv, ExistingMetadata, InjectOptions, Injection, ProjectConfig,
+    RecordVerification, RedactionMode, collect_env_vars_from_comma_list,
+    collect_env_vars_from_file, collect_whitelisted_env_vars,
+    collect_whitelisted_env_vars_with_file, expand_wheel_paths, internal_process_wheel,
+    process_wheels,
+};
+
+/// Inject build environment variables into Python wheel packages
+#[derive(Parser, Debug)]
+#[clap(author, version, about)]
+struct Args {
+    /// Wheel files to process, directories containing wheels, or glob patterns
+    #[clap(required = true)]
+    wheels: Vec<String>,
+
+    /// Output file path when processing a single wheel (default: overwrites input)
+    #[clap(short, long, conflicts_with = "output_dir")]
+    output: Option<String>,
+
+    /// Directory to write the processed wheels to (default: overwrites inputs)
+    #[clap(long)]
+    output_dir: Option<PathBuf>,
+
+    /// Number of wheels to process in parallel (default: number of CPUs)
+    #[clap(short, long)]
+    jobs: Option<usize>,
+
+    /// Path to file containing list of environment variables to collect
+    #[clap(short, long)]
+    env_file: Option<String>,
+
+    /// Fall back to the default whitelist if the env file cannot be read or is
+    /// invalid, instead of failing
+    #[clap(long, requires = "env_file")]
+    lenient_env_file: bool,
+
+    /// Comma-separated list of environment variables to collect, which may contain
+    /// `CUDA_*` globs, `re:` regular expressions and `!` negations
+    #[clap(short = 'v', long)]
+    env_vars: Option<String>,
+
+    /// Produce reproducible output using SOURCE_DATE_EPOCH (implied when
+    /// SOURCE_DATE_EPOCH is set)
+    #[clap(long)]
+    reproducible: bool,
+
+    /// What to do if the wheel already contains WHEEL.metadata: replace, merge or fail
+    /// (default: replace)
+    #[clap(long)]
+    existing: Option<ExistingMetadata>,
+
+    /// Check RECORD hashes and sizes against the wheel contents before and after
+    /// injection: strict, warn or off (default: strict)
+    #[clap(long)]
+    verify_record: Option<RecordVerification>,
+
+    /// What to do with variables whose name or value looks like a secret: mask,
+    /// refuse or off (default: mask)
+    #[clap(long)]
+    redact: Option<RedactionMode>,
+
+    /// Record this variable (or `*` pattern) verbatim even if it looks like a secret
+    #[clap(long, value_name = "NAME")]
+    allow_secret: Vec<String>,
+
+    /// Print the WHEEL.metadata content and RECORD line that would be written,
+    /// without modifying any wheel
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Record the URL of this git remote, in order of preference if repeated
+    /// (default: origin, then upstream)
+    #[clap(long, value_name = "NAME")]
+    git_remote: Vec<String>,
+
+    /// Also record the URLs of all git remotes
+    #[clap(long)]
+    all_remotes: bool,
+
+    /// Directory of the sources the wheels were built from, where git information
+    /// is read (default: the working directory)
+    #[clap(long)]
+    source_dir: Option<PathBuf>,
+
+    /// Ignore the [tool.wheel-metadata-injector] table of the project's pyproject.toml
+    #[clap(long)]
+    no_config: bool,
+
+    /// Maximum total uncompressed size of the wheel contents, in bytes
+    #[clap(long)]
+    max_total_size: Option<u64>,
+
+    /// Maximum compression ratio of a single wheel entry
+    #[clap(long)]
+    max_compression_ratio: Option<u64>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let wheel_paths = match expand_wheel_paths(&args.wheels) {
+        Ok(wheel_paths) => wheel_paths,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.output.is_some() && wheel_paths.len() > 1 {
+        eprintln!("Error: --output can only be used with a single wheel, use --output-dir");
+        return ExitCode::FAILURE;
+    }
+
+    let config = if args.no_config {
+        None
+    } else {
+        match env::current_dir()
+            .map_err(anyhow::Error::from)
+            .and_then(|dir| ProjectConfig::discover(&dir))
+        {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+    if let Some((path, _)) = &config {
+        println!("Using configuration from {}", path.display());
+    }
+    let config = config.map(|(_, config)| config);
+
+    let env = match collect_env(&args, config.as_ref()) {
+        Ok(env) => env,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if env.vars.is_empty() {
+        println!("Warning: No environment variables found to inject.");
+    } else {
+        println!("Found {} environment variables to inject", env.vars.len());
+        for (name, _) in &env.vars {
+            println!("  {}", name);
+        }
+    }
+    if !env.missing.is_empty() {
+        println!(
+            "Requested environment variables that are not set: {}",
+            env.missing.join(", ")
+        );
+    }
+
+    let mut limits = ArchiveLimits::default();
+    if let Some(max_total_size) = args.max_total_size {
+        limits.max_total_size = max_total_size;
+    }
+    if let Some(max_compression_ratio) = args.max_compression_ratio {
+        limits.max_compression_ratio = max_compression_ratio;
+    }
+
+    let mut options = InjectOptions {
+        limits,
+        dry_run: args.dry_run,
+        ..Default::default()
+    };
+    if let Some(config) = &config {
+        config.apply(&mut options);
+    }
+    options.reproducible |= args.reproducible;
+    if let Some(existing) = args.existing {
+        options.existing = existing;
+    }
+    if let Some(verify_record) = args.verify_record {
+        options.verify_record = verify_record;
+    }
+    if let Some(redact) = args.redact {
+        options.redaction.mode = redact;
+    }
+    options.redaction.allow.extend(args.allow_secret.clone());
+    if !args.git_remote.is_empty() {
+        options.git.remotes = args.git_remote.clone();
+    }
+    options.git.all_remotes |= args.all_remotes;
+    if args.source_dir.is_some() {
+        options.git.source_dir = args.source_dir.clone();
+    }
+
+    if let ([wheel_path], None) = (wheel_paths.as_slice(), &args.output_dir) {
+        let wheel_path = wheel_path.to_string_lossy();
+        let output_path = args.output.as_deref().unwrap_or(&wheel_path);
+
+        println!("Processing wheel: {}", wheel_path);
+
+        return match internal_process_wheel(&wheel_path, output_path, &env, &options) {
+            Ok(injection) if args.dry_run => {
+                print_injection(&injection);
+                println!("Dry run, {} was not modified", output_path);
+                ExitCode::SUCCESS
+            }
+            Ok(_) => {
+                println!("Successfully processed wheel: {}", output_path);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let jobs = args.jobs.unwrap_or_else(|| {
+        thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    });
+    println!(
+        "Processing {} wheels with {} workers",
+        wheel_paths.len(),
+        jobs
+    );
+
+    let results = match process_wheels(
+        &wheel_paths,
+        args.output_dir.as_deref(),
+        &env,
+        &options,
+        jobs,
+    ) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.dry_run {
+        for result in &results {
+            if let Ok(injection) = &result.result {
+                println!("\n=== {} ===", result.wheel_path.display());
+                print_injection(injection);
+            }
+        }
+    }
+
+    println!();
+    let mut failed = 0;
+    for result in &results {
+        match &result.result {
+            Ok(_) if args.dry_run => {
+                println!("  OK      {} (dry run)", result.wheel_path.display())
+            }
+            Ok(_) => println!(
+                "  OK      {} -> {}",
+                result.wheel_path.display(),
+                result.output_path.display()
+            ),
+            Err(e) => {
+                failed += 1;
+                println!("  FAILED  {}: {}", result.wheel_path.display(), e);
+            }
+        }
+    }
+    println!(
+        "Processed {} wheels: {} succeeded, {} failed",
+        results.len(),
+        results.len() - failed,
+        failed
+    );
+
+    if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Collect the variables from the list given on the command line, the project
+/// configuration or the default whitelist, in that order of precedence
+fn collect_env(args: &Args, config: Option<&ProjectConfig>) -> anyhow::Result<CapturedEnv> {
+    if let Some(env_vars_list) = &args.env_vars {
+        println!("Using inline environment variable list: {}", env_vars_list);
+        return collect_env_vars_from_comma_list(env_vars_list);
+    }
+
+    if let Some(env_file) = &args.env_file {
+        println!("Reading environment variable names from file: {}", env_file);
+        if args.lenient_env_file {
+            return Ok(collect_whitelisted_env_vars_with_file(Some(env_file)));
+        }
+        return collect_env_vars_from_file(env_file);
+    }
+
+    if let Some(env) = config
+        .map(ProjectConfig::collect_env_vars)
+        .transpose()?
+        .flatten()
+    {
+        println!("Using environment variable list from project configuration");
+        return Ok(env);
+    }
+
+    println!("Using default whitelisted environment variables");
+    Ok(collect_whitelisted_env_vars())
+}
+
+fn print_injection(injection: &Injection) {
+    println!("Would write {}:", injection.build_env_path);
+    print!("{}", injection.build_env_content);
+    println!("Would add RECORD line:");
+    println!("{}", injection.record_line);
+}
@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+
+use common::EnvValue;
+use regex::Regex;
+
+use crate::transform::{Transform, apply_transforms};
+
+/// One entry of an environment variable list
+#[derive(Debug, Clone)]
+pub enum VarPattern {
+    /// An exact variable name.
+    Name(String),
+    /// A name pattern where `*` matches any run of characters, e.g. `CUDA_*`.
+    Glob(String),
+    /// A regular expression prefixed with `re:`, e.g. `re:^NVCC_.*`.
+    Regex(Regex),
+}
+
+impl VarPattern {
+    pub fn parse(pattern: &str) -> anyhow::Result<Self> {
+        if let Some(regex) = pattern.strip_prefix("re:") {
+            let regex = Regex::new(regex)
+                .map_err(|e| anyhow::anyhow!("Invalid pattern '{}': {}", pattern, e))?;
+            Ok(VarPattern::Regex(regex))
+        } else if pattern.contains('*') {
+            Ok(VarPattern::Glob(pattern.to_string()))
+        } else {
+            Ok(VarPattern::Name(pattern.to_string()))
+        }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            VarPattern::Name(expected) => expected == name,
+            VarPattern::Glob(pattern) => matches_name_pattern(pattern, name),
+            VarPattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// The variables selected by a list of names and patterns, where entries starting
+/// with `!` exclude matching variables regardless of their position in the list.
+/// Entries may declare transforms for the values they select after `|`, e.g.
+/// `CMAKE_ARGS | trim | rewrite-paths`.
+#[derive(Debug, Clone, Default)]
+pub struct VarSelector {
+    include: Vec<(VarPattern, Vec<Transform>)>,
+    exclude: Vec<VarPattern>,
+}
+
+impl VarSelector {
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> anyhow::Result<Self> {
+        let mut selector = VarSelector::default();
+
+        for entry in entries {
+            let (pattern, transforms) = split_transforms(entry.as_ref().trim())?;
+            match pattern.strip_prefix('!') {
+                Some(_) if !transforms.is_empty() => {
+                    anyhow::bail!("Excluded pattern '{}' cannot have transforms", pattern)
+                }
+                Some(negated) => selector.exclude.push(VarPattern::parse(negated.trim())?),
+                None => selector
+                    .include
+                    .push((VarPattern::parse(pattern)?, transforms)),
+            }
+        }
+
+        Ok(selector)
+    }
+
+    /// Select variables from `env` and apply their transforms. Exact names keep
+    /// their position in the list, variables matched by a pattern are added in name
+    /// order at the position of the first pattern matching them.
+    pub fn select(
+        &self,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Vec<(String, EnvValue)> {
+        let env: BTreeMap<String, String> = env.into_iter().collect();
+        let mut selected: Vec<(String, EnvValue)> = Vec::new();
+
+        for (pattern, transforms) in &self.include {
+            let matches: Vec<(&String, &String)> = match pattern {
+                VarPattern::Name(name) => env.get_key_value(name).into_iter().collect(),
+                _ => env
+                    .iter()
+                    .filter(|(name, _)| pattern.matches(name))
+                    .collect(),
+            };
+
+            for (name, value) in matches {
+                let excluded = self.exclude.iter().any(|pattern| pattern.matches(name));
+                if !excluded && !selected.iter().any(|(selected, _)| selected == name) {
+                    selected.push((name.clone(), apply_transforms(value.clone(), transforms)));
+                }
+            }
+        }
+
+        selected
+    }
+
+    /// The exact names in the list that are missing from `selected`, i.e. were not
+    /// set in the environment they were selected from. Excluded names are not
+    /// considered requested.
+    pub fn missing(&self, selected: &[(String, EnvValue)]) -> Vec<String> {
+        let mut missing: Vec<String> = Vec::new();
+
+        for (pattern, _) in &self.include {
+            if let VarPattern::Name(name) = pattern {
+                let found = selected.iter().any(|(selected, _)| selected == name);
+                let excluded = self.exclude.iter().any(|pattern| pattern.matches(name));
+                if !found && !excluded && !missing.contains(name) {
+                    missing.push(name.clone());
+                }
+            }
+        }
+
+        missing
+    }
+}
+
+// Split `entry` into its pattern and the transforms following it. A regular
+// expression may itself contain `|`, so only the trailing parts that are valid
+// transforms are taken from it.
+fn split_transforms(entry: &str) -> anyhow::Result<(&str, Vec<Transform>)> {
+    let mut parts: Vec<&str> = entry.split('|').collect();
+    let mut transforms = Vec::new();
+
+    if entry.starts_with("re:") {
+        while parts.len() > 1 {
+            match parts[parts.len() - 1].trim().parse() {
+                Ok(transform) => transforms.push(transform),
+                Err(_) => break,
+            }
+            parts.pop();
+        }
+        transforms.reverse();
+        let pattern_len = parts.iter().map(|part| part.len() + 1).sum::<usize>() - 1;
+        return Ok((entry[..pattern_len].trim_end(), transforms));
+    }
+
+    for part in &parts[1..] {
+        let transform = part
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid entry '{}': {}", entry, e))?;
+        transforms.push(transform);
+    }
+
+    Ok((parts[0].trim_end(), transforms))
+}
+
+/// Match a variable name against a pattern where `*` matches any run of characters
+pub fn matches_name_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*` in the pattern
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> Vec<(String, String)> {
+        [
+            ("PATH", "/usr/bin"),
+            ("CUDA_VERSION", "12.1"),
+            ("CUDA_HOME", "/usr/local/cuda"),
+            ("TORCH_CUDA_ARCH_LIST", "8.0;9.0"),
+            ("NVCC_APPEND_FLAGS", "-allow-unsupported-compiler"),
+            ("NVCC_THREADS", "4"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+    }
+
+    fn names(selected: &[(String, EnvValue)]) -> Vec<&str> {
+        selected.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn matches_name_patterns() {
+        assert!(matches_name_pattern("*_TOKEN", "PYPI_TOKEN"));
+        assert!(!matches_name_pattern("*_TOKEN", "TOKEN"));
+        assert!(matches_name_pattern("AWS_*", "AWS_SECRET_ACCESS_KEY"));
+        assert!(matches_name_pattern("*_TOKEN_*", "CI_JOB_TOKEN_FILE"));
+        assert!(matches_name_pattern("GITHUB_TOKEN", "GITHUB_TOKEN"));
+        assert!(!matches_name_pattern("GITHUB_TOKEN", "GITHUB_TOKENS"));
+        assert!(matches_name_pattern("A*B*C", "AxxBxxC"));
+        assert!(!matches_name_pattern("A*B*C", "AxxC"));
+    }
+
+    #[test]
+    fn selects_globs_regexes_and_negations() {
+        let selector =
+            VarSelector::parse(&["TORCH_*", "CUDA_*", "!CUDA_HOME", "re:^NVCC_.*", "PATH"])
+                .unwrap();
+
+        assert_eq!(
+            names(&selector.select(env())),
+            [
+                "TORCH_CUDA_ARCH_LIST",
+                "CUDA_VERSION",
+                "NVCC_APPEND_FLAGS",
+                "NVCC_THREADS",
+                "PATH"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_exact_names_in_list_order() {
+        let selector =
+            VarSelector::parse(&["PATH", "MISSING", "*_VERSION", "CUDA_VERSION"]).unwrap();
+
+        let selected = selector.select(env());
+        assert_eq!(names(&selected), ["PATH", "CUDA_VERSION"]);
+        assert_eq!(selector.missing(&selected), ["MISSING"]);
+    }
+
+    #[test]
+    fn rejects_invalid_regex() {
+        assert!(VarSelector::parse(&["re:(unclosed"]).is_err());
+    }
+
+    #[test]
+    fn parses_transforms_after_patterns() {
+        let selector = VarSelector::parse(&[
+            "TORCH_CUDA_ARCH_LIST | trim | split=;",
+            "re:^(PATH|X)$ | split=:",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            selector.select(env()),
+            [
+                (
+                    "TORCH_CUDA_ARCH_LIST".to_string(),
+                    EnvValue::List(vec!["8.0".into(), "9.0".into()])
+                ),
+                ("PATH".to_string(), EnvValue::List(vec!["/usr/bin".into()])),
+            ]
+        );
+
+        assert!(VarSelector::parse(&["CFLAGS | upper"]).is_err());
+        assert!(VarSelector::parse(&["!CUDA_HOME | trim"]).is_err());
+    }
+}
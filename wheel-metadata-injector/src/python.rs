@@ -0,0 +1,441 @@
+use pyo3::exceptions::{PyFileNotFoundError, PyOSError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyIterator};
+
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use common::{EnvSource, EnvValue};
+
+use crate::{
+    CapturedEnv, InjectOptions, ProjectConfig, collect_env_vars_from_comma_list,
+    collect_env_vars_from_file, collect_whitelisted_env_vars,
+    collect_whitelisted_env_vars_with_file, internal_process_wheel,
+};
+
+/// A captured value as seen from Python: a `str`, or a `list[str]` for variables
+/// that were split
+#[derive(FromPyObject, IntoPyObject)]
+enum PyEnvValue {
+    String(String),
+    List(Vec<String>),
+}
+
+impl From<EnvValue> for PyEnvValue {
+    fn from(value: EnvValue) -> Self {
+        match value {
+            EnvValue::String(value) => PyEnvValue::String(value),
+            EnvValue::List(values) => PyEnvValue::List(values),
+        }
+    }
+}
+
+impl From<PyEnvValue> for EnvValue {
+    fn from(value: PyEnvValue) -> Self {
+        match value {
+            PyEnvValue::String(value) => EnvValue::String(value),
+            PyEnvValue::List(values) => EnvValue::List(values),
+        }
+    }
+}
+
+fn to_py_env_vars(env_vars: Vec<(String, EnvValue)>) -> Vec<(String, PyEnvValue)> {
+    env_vars
+        .into_iter()
+        .map(|(name, value)| (name, value.into()))
+        .collect()
+}
+
+/// Variables passed from Python: a `CapturedEnv` returned by one of the `get_*`
+/// functions, or `(name, value)` pairs
+#[derive(FromPyObject)]
+enum PyEnvVars<'py> {
+    Captured(PyRef<'py, PyCapturedEnv>),
+    Pairs(Vec<(String, PyEnvValue)>),
+}
+
+fn from_py_env_vars(
+    env_vars: Option<PyEnvVars<'_>>,
+    config: Option<&PyProjectConfig>,
+) -> PyResult<CapturedEnv> {
+    match env_vars {
+        Some(PyEnvVars::Captured(env)) => Ok(env.env.clone()),
+        Some(PyEnvVars::Pairs(env_vars)) => Ok(env_vars
+            .into_iter()
+            .map(|(name, value)| (name, value.into()))
+            .collect::<Vec<_>>()
+            .into()),
+        None => default_env_vars(config),
+    }
+}
+
+/// Captured environment variables. Iterating yields `(name, value)` pairs; the
+/// variables that were requested but not set and where the request came from are
+/// kept along, so that passing the object on records them in `WHEEL.metadata`.
+#[pyclass(name = "CapturedEnv", frozen)]
+struct PyCapturedEnv {
+    env: CapturedEnv,
+}
+
+#[pymethods]
+impl PyCapturedEnv {
+    /// The captured `(name, value)` pairs
+    #[getter]
+    fn vars(&self) -> Vec<(String, PyEnvValue)> {
+        to_py_env_vars(self.env.vars.clone())
+    }
+
+    /// Variables requested by name that were not set
+    #[getter]
+    fn missing(&self) -> Vec<String> {
+        self.env.missing.clone()
+    }
+
+    /// Where the list of requested variables came from: `"default"`, `"file"`,
+    /// `"inline"` or `"config"`, `None` if the variables were given directly
+    #[getter]
+    fn source(&self) -> Option<&'static str> {
+        self.env.source.map(|source| match source {
+            EnvSource::Default => "default",
+            EnvSource::File => "file",
+            EnvSource::Inline => "inline",
+            EnvSource::Config => "config",
+        })
+    }
+
+    fn __len__(&self) -> usize {
+        self.env.vars.len()
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        self.vars().into_pyobject(py)?.try_iter()
+    }
+}
+
+impl From<CapturedEnv> for PyCapturedEnv {
+    fn from(env: CapturedEnv) -> Self {
+        PyCapturedEnv { env }
+    }
+}
+
+/// The variables configured for the project, or the default whitelist
+fn default_env_vars(config: Option<&PyProjectConfig>) -> PyResult<CapturedEnv> {
+    let env = config
+        .map(|config| config.config.collect_env_vars())
+        .transpose()
+        .map_err(env_file_error)?
+        .flatten();
+
+    Ok(env.unwrap_or_else(collect_whitelisted_env_vars))
+}
+
+/// The `[tool.wheel-metadata-injector]` configuration of a project
+#[pyclass(name = "ProjectConfig", frozen)]
+struct PyProjectConfig {
+    path: PathBuf,
+    config: ProjectConfig,
+}
+
+#[pymethods]
+impl PyProjectConfig {
+    /// The pyproject.toml the configuration was read from
+    #[getter]
+    fn path(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+
+    /// The variables configured with `env-vars` or `env-file`, `None` if neither
+    /// is set
+    fn collect_env_vars(&self) -> PyResult<Option<PyCapturedEnv>> {
+        let env = self.config.collect_env_vars().map_err(env_file_error)?;
+
+        Ok(env.map(PyCapturedEnv::from))
+    }
+}
+
+/// Load the `[tool.wheel-metadata-injector]` table of the nearest pyproject.toml in
+/// `start_dir` (default: the working directory) or its ancestors, `None` if there
+/// is none
+#[pyfunction]
+#[pyo3(signature = (start_dir=None))]
+fn load_project_config(start_dir: Option<String>) -> PyResult<Option<PyProjectConfig>> {
+    let start_dir = match start_dir {
+        Some(start_dir) => PathBuf::from(start_dir),
+        None => env::current_dir()?,
+    };
+
+    let config = ProjectConfig::discover(&start_dir)
+        .map_err(|e| PyValueError::new_err(format!("{:#}", e)))?;
+
+    Ok(config.map(|(path, config)| PyProjectConfig { path, config }))
+}
+
+#[pyfunction]
+fn get_env_vars_from_comma_list(comma_list: String) -> PyResult<PyCapturedEnv> {
+    collect_env_vars_from_comma_list(&comma_list)
+        .map(PyCapturedEnv::from)
+        .map_err(|e| PyValueError::new_err(format!("{:#}", e)))
+}
+
+#[pyfunction]
+#[pyo3(signature = (wheel_path, env_vars, output_path=None, **options))]
+fn process_wheel_with_env_vars(
+    wheel_path: String,
+    env_vars: String,
+    output_path: Option<String>,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<String> {
+    let output_path = output_path.unwrap_or_else(|| wheel_path.clone());
+
+    let env_vars = collect_env_vars_from_comma_list(&env_vars)
+        .map_err(|e| PyValueError::new_err(format!("{:#}", e)))?;
+    let (options, _) = inject_options(options)?;
+
+    match internal_process_wheel(&wheel_path, &output_path, &env_vars, &options) {
+        Ok(_) => Ok(output_path),
+        Err(e) => Err(PyValueError::new_err(format!(
+            "Error processing wheel: {}",
+            e
+        ))),
+    }
+}
+
+#[pymodule]
+fn _wheel_metadata_injector(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyCapturedEnv>()?;
+    m.add_class::<PyProjectConfig>()?;
+    m.add_function(wrap_pyfunction!(process_wheel, m)?)?;
+    m.add_function(wrap_pyfunction!(process_wheel_with_env_file, m)?)?;
+    m.add_function(wrap_pyfunction!(process_wheel_with_env_vars, m)?)?;
+    m.add_function(wrap_pyfunction!(process_wheels, m)?)?;
+    m.add_function(wrap_pyfunction!(expand_wheel_paths, m)?)?;
+    m.add_function(wrap_pyfunction!(preview_wheel, m)?)?;
+    m.add_function(wrap_pyfunction!(get_whitelisted_env_vars, m)?)?;
+    m.add_function(wrap_pyfunction!(get_whitelisted_env_vars_with_file, m)?)?;
+    m.add_function(wrap_pyfunction!(get_env_vars_from_comma_list, m)?)?;
+    m.add_function(wrap_pyfunction!(load_project_config, m)?)?;
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(signature = (wheel_path, output_path=None, **options))]
+fn process_wheel(
+    wheel_path: String,
+    output_path: Option<String>,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<String> {
+    let output_path = output_path.unwrap_or_else(|| wheel_path.clone());
+
+    let (options, config) = inject_options(options)?;
+    let env_vars = default_env_vars(config.as_deref())?;
+
+    match internal_process_wheel(&wheel_path, &output_path, &env_vars, &options) {
+        Ok(_) => Ok(output_path),
+        Err(e) => Err(PyValueError::new_err(format!(
+            "Error processing wheel: {}",
+            e
+        ))),
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (wheel_path, env_file, output_path=None, *, strict=true, **options))]
+fn process_wheel_with_env_file(
+    wheel_path: String,
+    env_file: String,
+    output_path: Option<String>,
+    strict: bool,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<String> {
+    let output_path = output_path.unwrap_or_else(|| wheel_path.clone());
+
+    let env_vars = env_vars_from_file(&env_file, strict)?;
+    let (options, _) = inject_options(options)?;
+
+    match internal_process_wheel(&wheel_path, &output_path, &env_vars, &options) {
+        Ok(_) => Ok(output_path),
+        Err(e) => Err(PyValueError::new_err(format!(
+            "Error processing wheel: {}",
+            e
+        ))),
+    }
+}
+
+/// Process several wheels in parallel, returning `(wheel_path, output_path, error)`
+/// for each wheel, with `error` set to `None` on success
+#[pyfunction]
+#[pyo3(signature = (wheel_paths, env_vars=None, *, output_dir=None, jobs=None, **options))]
+fn process_wheels(
+    py: Python<'_>,
+    wheel_paths: Vec<String>,
+    env_vars: Option<PyEnvVars<'_>>,
+    output_dir: Option<String>,
+    jobs: Option<usize>,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Vec<(String, String, Option<String>)>> {
+    let (options, config) = inject_options(options)?;
+    let env_vars = from_py_env_vars(env_vars, config.as_deref())?;
+    let jobs = jobs.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+
+    let results = py
+        .allow_threads(|| {
+            let wheel_paths = crate::expand_wheel_paths(&wheel_paths)?;
+            crate::process_wheels(
+                &wheel_paths,
+                output_dir.as_deref().map(Path::new),
+                &env_vars,
+                &options,
+                jobs,
+            )
+        })
+        .map_err(|e| PyValueError::new_err(format!("Error processing wheels: {}", e)))?;
+
+    Ok(results
+        .into_iter()
+        .map(|result| {
+            (
+                result.wheel_path.to_string_lossy().into_owned(),
+                result.output_path.to_string_lossy().into_owned(),
+                result.result.err().map(|e| e.to_string()),
+            )
+        })
+        .collect())
+}
+
+/// Expand directories and glob patterns into the wheel paths they contain, the
+/// same way `process_wheels` does
+#[pyfunction]
+fn expand_wheel_paths(inputs: Vec<String>) -> PyResult<Vec<String>> {
+    let wheel_paths =
+        crate::expand_wheel_paths(&inputs).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(wheel_paths
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect())
+}
+
+/// Return the `WHEEL.metadata` content and the RECORD line that processing the
+/// wheel would write, without modifying it
+#[pyfunction]
+#[pyo3(signature = (wheel_path, env_vars=None, **options))]
+fn preview_wheel(
+    wheel_path: String,
+    env_vars: Option<PyEnvVars<'_>>,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<(String, String)> {
+    let (options, config) = inject_options(options)?;
+    let env_vars = from_py_env_vars(env_vars, config.as_deref())?;
+    let options = InjectOptions {
+        dry_run: true,
+        ..options
+    };
+
+    match internal_process_wheel(&wheel_path, &wheel_path, &env_vars, &options) {
+        Ok(injection) => Ok((injection.build_env_content, injection.record_line)),
+        Err(e) => Err(PyValueError::new_err(format!(
+            "Error processing wheel: {}",
+            e
+        ))),
+    }
+}
+
+/// Options from the keyword arguments shared by the processing functions, on top
+/// of the `config` project configuration and then the defaults. Arguments that are
+/// `None` are ignored. The project configuration is returned as well.
+fn inject_options<'py>(
+    kwargs: Option<&Bound<'py, PyDict>>,
+) -> PyResult<(InjectOptions, Option<PyRef<'py, PyProjectConfig>>)> {
+    let mut options = InjectOptions::default();
+    let Some(kwargs) = kwargs else {
+        return Ok((options, None));
+    };
+
+    let config: Option<PyRef<'py, PyProjectConfig>> = match kwargs.get_item("config")? {
+        Some(config) => extract_option("config", &config)?,
+        None => None,
+    };
+    if let Some(config) = &config {
+        config.config.apply(&mut options);
+    }
+
+    for (key, value) in kwargs {
+        if value.is_none() {
+            continue;
+        }
+        let key: String = key.extract()?;
+        match key.as_str() {
+            "config" => {}
+            "reproducible" => options.reproducible = extract_option(&key, &value)?,
+            "existing" => {
+                let existing: String = extract_option(&key, &value)?;
+                options.existing = existing.parse().map_err(PyValueError::new_err)?;
+            }
+            "verify_record" => {
+                let verify_record: String = extract_option(&key, &value)?;
+                options.verify_record = verify_record.parse().map_err(PyValueError::new_err)?;
+            }
+            "redact" => {
+                let redact: String = extract_option(&key, &value)?;
+                options.redaction.mode = redact.parse().map_err(PyValueError::new_err)?;
+            }
+            "allow_secrets" => {
+                let allow_secrets: Vec<String> = extract_option(&key, &value)?;
+                options.redaction.allow.extend(allow_secrets);
+            }
+            "git_remotes" => options.git.remotes = extract_option(&key, &value)?,
+            "all_remotes" => options.git.all_remotes = extract_option(&key, &value)?,
+            "source_dir" => options.git.source_dir = Some(extract_option(&key, &value)?),
+            "max_total_size" => options.limits.max_total_size = extract_option(&key, &value)?,
+            "max_compression_ratio" => {
+                options.limits.max_compression_ratio = extract_option(&key, &value)?
+            }
+            _ => {
+                return Err(PyTypeError::new_err(format!(
+                    "unexpected keyword argument '{}'",
+                    key
+                )));
+            }
+        }
+    }
+
+    Ok((options, config))
+}
+
+fn extract_option<'py, T: FromPyObject<'py>>(name: &str, value: &Bound<'py, PyAny>) -> PyResult<T> {
+    value
+        .extract()
+        .map_err(|e| PyTypeError::new_err(format!("argument '{}': {}", name, e)))
+}
+
+#[pyfunction]
+fn get_whitelisted_env_vars() -> PyResult<PyCapturedEnv> {
+    Ok(collect_whitelisted_env_vars().into())
+}
+
+#[pyfunction]
+#[pyo3(signature = (env_file, strict=true))]
+fn get_whitelisted_env_vars_with_file(env_file: String, strict: bool) -> PyResult<PyCapturedEnv> {
+    Ok(env_vars_from_file(&env_file, strict)?.into())
+}
+
+/// Collect the variables listed in `env_file`. When `strict`, a missing file raises
+/// `FileNotFoundError`, other read errors `OSError` and invalid entries
+/// `ValueError`; otherwise the default whitelist is used instead.
+fn env_vars_from_file(env_file: &str, strict: bool) -> PyResult<CapturedEnv> {
+    if !strict {
+        return Ok(collect_whitelisted_env_vars_with_file(Some(env_file)));
+    }
+
+    collect_env_vars_from_file(env_file).map_err(env_file_error)
+}
+
+fn env_file_error(e: anyhow::Error) -> PyErr {
+    let message = format!("{:#}", e);
+    match e.downcast_ref::<io::Error>().map(io::Error::kind) {
+        Some(io::ErrorKind::NotFound) => PyFileNotFoundError::new_err(message),
+        Some(_) => PyOSError::new_err(message),
+        None => PyValueError::new_err(message),
+    }
+}
@@ -0,0 +1,424 @@
+use anyhow::Context;
+use csv::{ReaderBuilder, Terminator, WriterBuilder};
+use sha2::{Digest, Sha256};
+
+use crate::verify::encode_record_digest;
+
+/// A single `path,hash,size` row of a wheel's RECORD file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordEntry {
+    /// Path of the file inside the wheel.
+    pub path: String,
+    /// `algorithm=digest`, absent for RECORD itself and its signatures.
+    pub hash: Option<String>,
+    /// Size of the file in bytes, absent for RECORD itself and its signatures.
+    pub size: Option<u64>,
+}
+
+impl RecordEntry {
+    /// Build the entry for a file with the given contents, hashed with sha256
+    pub fn for_data(path: &str, data: &[u8]) -> Self {
+        let digest = Sha256::digest(data);
+
+        RecordEntry {
+            path: path.to_string(),
+            hash: Some(format!("sha256={}", encode_record_digest(&digest))),
+            size: Some(data.len() as u64),
+        }
+    }
+
+    /// The entry as a RECORD line, without line terminator
+    pub fn to_line(&self) -> anyhow::Result<String> {
+        let line = serialize_entries(std::slice::from_ref(self), Terminator::Any(b'\n'))?;
+
+        Ok(line.trim_end_matches('\n').to_string())
+    }
+}
+
+/// A parsed RECORD file.
+///
+/// RECORD is a headerless CSV file, so paths containing commas or quotes are quoted
+/// and either line ending may be used. Serializing keeps the line ending of the
+/// parsed file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+    entries: Vec<RecordEntry>,
+    crlf: bool,
+}
+
+impl Record {
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(content.as_bytes());
+
+        let mut entries = Vec::new();
+        for (i, row) in reader.records().enumerate() {
+            let row = row.with_context(|| format!("Failed to parse RECORD line {}", i + 1))?;
+
+            // A blank line at the end of the file reads as a single empty field
+            if row.len() == 1 && row[0].is_empty() {
+                continue;
+            }
+
+            if row.len() != 3 {
+                anyhow::bail!(
+                    "RECORD line {} has {} fields, expected path,hash,size",
+                    i + 1,
+                    row.len()
+                );
+            }
+
+            let size = match &row[2] {
+                "" => None,
+                size => Some(size.parse::<u64>().with_context(|| {
+                    format!("Invalid size '{}' on RECORD line {}", size, i + 1)
+                })?),
+            };
+
+            entries.push(RecordEntry {
+                path: row[0].to_string(),
+                hash: Some(&row[1])
+                    .filter(|hash| !hash.is_empty())
+                    .map(String::from),
+                size,
+            });
+        }
+
+        Ok(Record {
+            entries,
+            crlf: content.contains("\r\n"),
+        })
+    }
+
+    pub fn entries(&self) -> &[RecordEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, path: &str) -> Option<&RecordEntry> {
+        self.entries.iter().find(|entry| entry.path == path)
+    }
+
+    /// Replace the entry for the same path in place, dropping any duplicates of it,
+    /// or append the entry if the path is not listed yet
+    pub fn set(&mut self, entry: RecordEntry) {
+        match self.entries.iter().position(|e| e.path == entry.path) {
+            Some(index) => {
+                let path = entry.path.clone();
+                self.entries[index] = entry;
+
+                let mut seen = false;
+                self.entries.retain(|e| {
+                    if e.path != path {
+                        return true;
+                    }
+                    let first = !seen;
+                    seen = true;
+                    first
+                });
+            }
+            None => self.entries.push(entry),
+        }
+    }
+
+    pub fn serialize(&self) -> anyhow::Result<String> {
+        let terminator = if self.crlf {
+            Terminator::CRLF
+        } else {
+            Terminator::Any(b'\n')
+        };
+
+        serialize_entries(&self.entries, terminator)
+    }
+}
+
+fn serialize_entries(entries: &[RecordEntry], terminator: Terminator) -> anyhow::Result<String> {
+    let mut writer = WriterBuilder::new()
+        .has_headers(false)
+        .terminator(terminator)
+        .from_writer(Vec::new());
+
+    for entry in entries {
+        let size = entry.size.map(|size| size.to_string()).unwrap_or_default();
+        writer.write_record([
+            entry.path.as_str(),
+            entry.hash.as_deref().unwrap_or(""),
+            size.as_str(),
+        ])?;
+    }
+
+    let content = writer.into_inner().map_err(|e| e.into_error())?;
+
+    Ok(String::from_utf8(content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From example-21.12-py3-none-any.whl in CPython's importlib test data, built by
+    // bdist_wheel 0.33.4: csv.writer rows with "\n" line endings, RECORD last
+    const SETUPTOOLS_RECORD: &str = "\
+example/__init__.py,sha256=7zgBgsryxO9WvrUg7BuHr_5pkXJ-EbgOcahFidZ8RXo,33
+example-21.12.dist-info/METADATA,sha256=GXj1dn_3aEUtB6IaklQNC0uaNvVo-55r1cZGBGqjwJ4,187
+example-21.12.dist-info/WHEEL,sha256=S8S5VL-stOTSZDYxHyf0KP7eds0J72qrK0Evu3TfyAY,92
+example-21.12.dist-info/entry_points.txt,sha256=3VBoHN-kz4oixYNmKYv_otavZH1onPYsy0yWzfzUgIc,65
+example-21.12.dist-info/top_level.txt,sha256=E1UDUKhoHITIYarC5bRAFhwrM6Pk8wKsaAyltobeSN4,8
+example-21.12.dist-info/RECORD,,
+";
+
+    // From example2-1.0.0-py3-none-any.whl in CPython's importlib test data, built by
+    // trampolim 0.0.3
+    const TRAMPOLIM_RECORD: &str = "\
+example2/__init__.py,sha256=7zgBgsryxO9WvrUg7BuHr_5pkXJ-EbgOcahFidZ8RXo,33
+example2-1.0.0.dist-info/METADATA,sha256=ktU-LKzerpL2l_JgTTk4LLJ5Zr5irKq6EAx4sbX3oyU,52
+example2-1.0.0.dist-info/WHEEL,sha256=w8rrM3j30TB9sW0tsDebITJSmFF6ABYBQIZJsiNF2Bc,85
+example2-1.0.0.dist-info/entrypoints.txt,sha256=gusyJOtWgPjrEQeih3JaRDoex3-fbcrDg4n6EPUVmuQ,43
+example2-1.0.0.dist-info/RECORD,,
+";
+
+    // The rows below are from RECORD files pip wrote when installing the named wheels.
+    // pip keeps each row of the wheel's RECORD but sorts them and writes "\r\n" line
+    // endings; the INSTALLER and __pycache__ rows it adds are dropped here.
+
+    // From typing_inspection-0.4.2-py3-none-any.whl, built by hatchling 1.27.0
+    const HATCHLING_RECORD: &str = "\
+typing_inspection-0.4.2.dist-info/METADATA,sha256=YQls0L_jxwQLb5jCKwLRkP4Bk20P92FsUTT-0CiRlTo,2552\r\n\
+typing_inspection-0.4.2.dist-info/RECORD,,\r\n\
+typing_inspection-0.4.2.dist-info/WHEEL,sha256=qtCwoSJWgHk21S1Kb4ihdzI2rlJ1ZKaIurTj_ngOhyQ,87\r\n\
+typing_inspection-0.4.2.dist-info/licenses/LICENSE,sha256=gEtZsl8sMb0nj5ICoZrkmjlFqiZkOH4tChKMfKzGHsM,1090\r\n\
+typing_inspection/__init__.py,sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0\r\n\
+typing_inspection/introspection.py,sha256=dD5Ad4J6hAfF6UBzBO4sqSs1h2ybQVThkQofLWWVBP0,22534\r\n\
+typing_inspection/py.typed,sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0\r\n\
+typing_inspection/typing_objects.py,sha256=kajVgh8J7UZ7wTidVxzFMpjwSnFGBoDoTqfVAAvOHZ8,17166\r\n\
+typing_inspection/typing_objects.pyi,sha256=u1NDpl_RJFnAUMAMx-WBd0SBKwVG7luLEc8ukSvRnZs,9401\r\n\
+";
+
+    // From rpds_py-0.30.0-cp311-cp311-manylinux_2_17_x86_64.manylinux2014_x86_64.whl,
+    // built by maturin 1.10.2
+    const MATURIN_RECORD: &str = "\
+rpds/__init__.py,sha256=w3MgXW7lpTCICw0KXbw20QX573_kbsEnWIeMsCAugvM,99\r\n\
+rpds/__init__.pyi,sha256=am7x6oMa_pu_kv1NlolqJbPr6_UvCvoyxGKrDGSMKEk,2602\r\n\
+rpds/py.typed,sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0\r\n\
+rpds/rpds.cpython-311-x86_64-linux-gnu.so,sha256=841bbIMRlStFAoxzeAy4_GEo_R8ahPNCECKciu6IGZU,1054384\r\n\
+rpds_py-0.30.0.dist-info/METADATA,sha256=zAHQl925x-B6KclVPujdNc-SDoX7V097DCPWgTinFr8,4145\r\n\
+rpds_py-0.30.0.dist-info/RECORD,,\r\n\
+rpds_py-0.30.0.dist-info/WHEEL,sha256=4-OGKj-lgfDeqzzIOb2pgfMuLfXCZdUla629tnbzHm4,147\r\n\
+rpds_py-0.30.0.dist-info/licenses/LICENSE,sha256=MU5Okb47qpPA-0vMyeTpfNZD64ObBlr5IXgsIXX-mQk,1057\r\n\
+";
+
+    // Excerpt from pyzmq-26.3.0-cp311-cp311-manylinux_2_28_x86_64.whl, built by
+    // scikit-build-core 0.11.0 and repaired by auditwheel, which added pyzmq.libs
+    const SCIKIT_BUILD_CORE_RECORD: &str = "\
+pyzmq-26.3.0.dist-info/METADATA,sha256=Hr5JwOTSpDRWmeiMB1wmnm1rUjdiYWQFXi1sSfeIWCA,6164\r\n\
+pyzmq-26.3.0.dist-info/RECORD,,\r\n\
+pyzmq-26.3.0.dist-info/WHEEL,sha256=opyeEgSCNuyiSAjduBdXw_7EzQQPeOKsr19UN7GhiSY,118\r\n\
+pyzmq-26.3.0.dist-info/licenses/LICENSE.md,sha256=wM9fXAP41ncveicd8ctnEFRXi9PXlSfHL8Hyj4zHKno,1545\r\n\
+pyzmq-26.3.0.dist-info/licenses/licenses/LICENSE.libsodium.txt,sha256=Q5ZNl2pts_uYavaJ0F-MoOmXGHi8yucJdQ2sj9xKmc8,823\r\n\
+pyzmq-26.3.0.dist-info/licenses/licenses/LICENSE.tornado.txt,sha256=z8d0m5b2O9McPEK1xHG_dWgUBT6EfBDz6wA0F7xSPTA,11358\r\n\
+pyzmq-26.3.0.dist-info/licenses/licenses/LICENSE.zeromq.txt,sha256=HyVuytGSiAUQ6ErWBHTqt1iSGHhLmlC8fO7jTCuR8dU,16725\r\n\
+pyzmq.libs/libsodium-321ab93b.so.26.2.0,sha256=JT2GrenKDzZyZBzwyGEbK1YR1Xt9jRkfK8heRiMqOe4,1209897\r\n\
+pyzmq.libs/libzmq-a45df3b1.so.5.2.5,sha256=vsDXTiN_O92vwvkPVPtQXx2HtgxctOGDa2fDtz-TB5k,950593\r\n\
+zmq/__init__.py,sha256=0zUxdN9mC6mBJAOkBfsI4em8roHbvH0afHQTYsMFjXA,2232\r\n\
+zmq/__init__.pyi,sha256=4JJGGKu9IVsRVES3jNr2_MrHXA9CW6rjAiZqLGaKmq8,960\r\n\
+zmq/backend/cython/__init__.pxd,sha256=iRgsrNY8-yEX3UL83jFHziSPaVibZx-qltTXcYVUM9Y,60\r\n\
+zmq/backend/cython/__init__.py,sha256=SMuE8GAtsmEMWNGrv-ZlQZcuTD3EVU838E1ViRHLDNI,322\r\n\
+zmq/backend/cython/_externs.pxd,sha256=0EM00v73_7Bp_9Z4qcCuwe0IIgoPgatYgHX9wrjhbJE,339\r\n\
+zmq/backend/cython/_zmq.cpython-311-x86_64-linux-gnu.so,sha256=HewHx2dXQhlWnaB1S9wK8w8qAZVmwJdMGQaf4WQUjN8,319937\r\n\
+zmq/backend/cython/_zmq.pxd,sha256=fv1mQ6DxnJghW5XgD45dOnokVVH1UDTV0Us5KYuBo28,2186\r\n\
+zmq/backend/cython/_zmq.py,sha256=PgojSlJb81xbwcetkdqi0l_d8jVeedxOyLTsGtbcLxk,58306\r\n\
+zmq/backend/cython/constant_enums.pxi,sha256=LNVbov9C6GBuJvWHnfpqUjmNT0x8alTeub885-o_mI0,7562\r\n\
+zmq/backend/cython/libzmq.pxd,sha256=ofccd3ZlZvJL7_Ud1gVPHTxl1PDO69UivxliA8QcD-w,4564\r\n\
+";
+
+    const WHEEL_METADATA_LINE: &str = "example-21.12.dist-info/WHEEL.metadata,sha256=bmANZripFuyE6PZd2tcBDH7WUTdY1zosSm88BwVmuNg,6";
+
+    #[test]
+    fn parses_setuptools_record() {
+        let record = Record::parse(SETUPTOOLS_RECORD).unwrap();
+
+        assert_eq!(record.entries().len(), 6);
+        assert_eq!(
+            record.get("example/__init__.py"),
+            Some(&RecordEntry {
+                path: "example/__init__.py".to_string(),
+                hash: Some("sha256=7zgBgsryxO9WvrUg7BuHr_5pkXJ-EbgOcahFidZ8RXo".to_string()),
+                size: Some(33),
+            })
+        );
+
+        let own = record.get("example-21.12.dist-info/RECORD").unwrap();
+        assert_eq!(own.hash, None);
+        assert_eq!(own.size, None);
+
+        assert_eq!(record.serialize().unwrap(), SETUPTOOLS_RECORD);
+    }
+
+    #[test]
+    fn parses_trampolim_record() {
+        let record = Record::parse(TRAMPOLIM_RECORD).unwrap();
+
+        assert_eq!(record.entries().len(), 5);
+        assert_eq!(
+            record
+                .get("example2-1.0.0.dist-info/entrypoints.txt")
+                .unwrap()
+                .size,
+            Some(43)
+        );
+        assert_eq!(record.serialize().unwrap(), TRAMPOLIM_RECORD);
+    }
+
+    #[test]
+    fn parses_hatchling_record() {
+        let record = Record::parse(HATCHLING_RECORD).unwrap();
+
+        assert_eq!(record.entries().len(), 9);
+        assert_eq!(
+            record
+                .get("typing_inspection-0.4.2.dist-info/licenses/LICENSE")
+                .unwrap()
+                .size,
+            Some(1090)
+        );
+        assert_eq!(record.serialize().unwrap(), HATCHLING_RECORD);
+    }
+
+    #[test]
+    fn parses_maturin_record() {
+        let record = Record::parse(MATURIN_RECORD).unwrap();
+
+        assert_eq!(record.entries().len(), 8);
+        assert_eq!(
+            record.get("rpds/rpds.cpython-311-x86_64-linux-gnu.so"),
+            Some(&RecordEntry {
+                path: "rpds/rpds.cpython-311-x86_64-linux-gnu.so".to_string(),
+                hash: Some("sha256=841bbIMRlStFAoxzeAy4_GEo_R8ahPNCECKciu6IGZU".to_string()),
+                size: Some(1054384),
+            })
+        );
+        assert_eq!(record.serialize().unwrap(), MATURIN_RECORD);
+    }
+
+    #[test]
+    fn parses_scikit_build_core_record() {
+        let mut record = Record::parse(SCIKIT_BUILD_CORE_RECORD).unwrap();
+
+        assert_eq!(record.entries().len(), 19);
+        assert_eq!(
+            record
+                .get("pyzmq.libs/libzmq-a45df3b1.so.5.2.5")
+                .unwrap()
+                .size,
+            Some(950593)
+        );
+        let own = record.get("pyzmq-26.3.0.dist-info/RECORD").unwrap();
+        assert_eq!(own.hash, None);
+        assert_eq!(own.size, None);
+        assert_eq!(record.serialize().unwrap(), SCIKIT_BUILD_CORE_RECORD);
+
+        record.set(RecordEntry::for_data(
+            "pyzmq-26.3.0.dist-info/WHEEL.metadata",
+            b"[env]\n",
+        ));
+        let serialized = record.serialize().unwrap();
+        assert!(serialized.starts_with(SCIKIT_BUILD_CORE_RECORD));
+        assert!(serialized.ends_with(
+            "pyzmq-26.3.0.dist-info/WHEEL.metadata,sha256=bmANZripFuyE6PZd2tcBDH7WUTdY1zosSm88BwVmuNg,6\r\n"
+        ));
+    }
+
+    #[test]
+    fn quotes_paths_with_commas_and_quotes() {
+        // None of the wheels above has such paths; csv.writer quotes them like this
+        let content = "\
+\"demo/data/a,b.txt\",sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0
+\"demo/data/say \"\"hi\"\".txt\",sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0
+demo-1.0.dist-info/RECORD,,
+";
+        let record = Record::parse(content).unwrap();
+
+        assert!(record.get("demo/data/a,b.txt").is_some());
+        assert!(record.get("demo/data/say \"hi\".txt").is_some());
+        assert_eq!(record.serialize().unwrap(), content);
+    }
+
+    #[test]
+    fn parses_record_without_trailing_newline() {
+        let mut record = Record::parse(SETUPTOOLS_RECORD.trim_end()).unwrap();
+
+        assert_eq!(record.entries().len(), 6);
+
+        record.set(RecordEntry::for_data(
+            "example-21.12.dist-info/WHEEL.metadata",
+            b"[env]\n",
+        ));
+        let serialized = record.serialize().unwrap();
+        assert!(serialized.contains("example-21.12.dist-info/RECORD,,\n"));
+        assert!(serialized.ends_with(&format!("{}\n", WHEEL_METADATA_LINE)));
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        // RECORD is read as CSV, which allows either line ending
+        let content = SETUPTOOLS_RECORD.replace('\n', "\r\n");
+        let mut record = Record::parse(&content).unwrap();
+
+        assert_eq!(
+            record.entries(),
+            Record::parse(SETUPTOOLS_RECORD).unwrap().entries()
+        );
+
+        record.set(RecordEntry::for_data(
+            "example-21.12.dist-info/WHEEL.metadata",
+            b"[env]\n",
+        ));
+        let serialized = record.serialize().unwrap();
+        assert!(serialized.starts_with(&content));
+        assert!(serialized.ends_with(&format!("{}\r\n", WHEEL_METADATA_LINE)));
+    }
+
+    #[test]
+    fn set_replaces_existing_entry_in_place() {
+        // A hex digest, as written by earlier versions of this tool, listed twice
+        let content = "\
+example-21.12.dist-info/WHEEL.metadata,sha256=6e600d66b8a916ec84e8f65ddad7010c7ed6513758d73a2c4a6f3c070566b8d8,6
+example/__init__.py,sha256=7zgBgsryxO9WvrUg7BuHr_5pkXJ-EbgOcahFidZ8RXo,33
+example-21.12.dist-info/WHEEL.metadata,sha256=6e600d66b8a916ec84e8f65ddad7010c7ed6513758d73a2c4a6f3c070566b8d8,6
+example-21.12.dist-info/RECORD,,
+";
+        let mut record = Record::parse(content).unwrap();
+        record.set(RecordEntry::for_data(
+            "example-21.12.dist-info/WHEEL.metadata",
+            b"[env]\n",
+        ));
+
+        let paths: Vec<_> = record.entries().iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "example-21.12.dist-info/WHEEL.metadata",
+                "example/__init__.py",
+                "example-21.12.dist-info/RECORD",
+            ]
+        );
+        assert_eq!(record.entries()[0].to_line().unwrap(), WHEEL_METADATA_LINE);
+    }
+
+    #[test]
+    fn formats_single_line() {
+        let entry = RecordEntry::for_data("demo/data/a,b.txt", b"");
+
+        assert_eq!(
+            entry.to_line().unwrap(),
+            "\"demo/data/a,b.txt\",sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_rows() {
+        assert!(Record::parse("demo/__init__.py,sha256=abc\n").is_err());
+        assert!(Record::parse("demo/__init__.py,sha256=abc,twelve\n").is_err());
+    }
+}
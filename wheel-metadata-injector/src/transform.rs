@@ -0,0 +1,182 @@
+use std::env;
+use std::str::FromStr;
+
+use common::EnvValue;
+
+/// A transform applied to a captured value before it is written, declared after
+/// the variable in a list, e.g. `CMAKE_ARGS | trim | rewrite-paths | split`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transform {
+    /// Strip leading and trailing whitespace.
+    Trim,
+    /// Replace the workspace and home directory prefixes of absolute paths with
+    /// `<workspace>` and `<home>`.
+    RewritePaths,
+    /// Split the value into a list on a separator (`split=;`), or on whitespace
+    /// (`split`). Empty items are dropped.
+    Split(Option<String>),
+}
+
+impl FromStr for Transform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            None if s == "trim" => Ok(Transform::Trim),
+            None if s == "rewrite-paths" => Ok(Transform::RewritePaths),
+            None if s == "split" => Ok(Transform::Split(None)),
+            Some(("split", separator)) if !separator.is_empty() => {
+                Ok(Transform::Split(Some(separator.to_string())))
+            }
+            _ => Err(format!(
+                "invalid transform '{}', expected one of: trim, rewrite-paths, split, split=<separator>",
+                s
+            )),
+        }
+    }
+}
+
+/// Apply `transforms` to `value` in order. Transforms following a split apply to
+/// each item of the list.
+pub fn apply_transforms(value: String, transforms: &[Transform]) -> EnvValue {
+    let mut values = vec![value];
+    let mut split = false;
+
+    for transform in transforms {
+        values = match transform {
+            Transform::Trim => values.iter().map(|v| v.trim().to_string()).collect(),
+            Transform::RewritePaths => {
+                let prefixes = path_prefixes();
+                values.iter().map(|v| rewrite_paths(v, &prefixes)).collect()
+            }
+            Transform::Split(separator) => {
+                split = true;
+                values
+                    .iter()
+                    .flat_map(|v| -> Vec<&str> {
+                        match separator {
+                            Some(separator) => v.split(separator.as_str()).collect(),
+                            None => v.split_whitespace().collect(),
+                        }
+                    })
+                    .filter(|item| !item.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+        };
+    }
+
+    if split {
+        EnvValue::List(values)
+    } else {
+        EnvValue::String(values.pop().unwrap_or_default())
+    }
+}
+
+/// The directory prefixes replaced by `rewrite-paths`, longest first so that a
+/// workspace inside the home directory is rewritten as the workspace
+fn path_prefixes() -> Vec<(String, &'static str)> {
+    let workspace = ["GITHUB_WORKSPACE", "CI_PROJECT_DIR"]
+        .iter()
+        .find_map(|name| env::var(name).ok())
+        .or_else(|| {
+            env::current_dir()
+                .ok()
+                .map(|dir| dir.to_string_lossy().into_owned())
+        });
+
+    let mut prefixes: Vec<(String, &'static str)> = [
+        (workspace, "<workspace>"),
+        (env::var("HOME").ok(), "<home>"),
+    ]
+    .into_iter()
+    .filter_map(|(prefix, replacement)| {
+        let prefix = prefix?.trim_end_matches('/').to_string();
+        // Rewriting `/` would mangle every path
+        (!prefix.is_empty()).then_some((prefix, replacement))
+    })
+    .collect();
+
+    prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+    prefixes
+}
+
+/// Replace each of `prefixes` in `value` where it is a whole path, i.e. followed by
+/// `/` or by a character that cannot be part of a path component
+fn rewrite_paths(value: &str, prefixes: &[(String, &str)]) -> String {
+    let mut rewritten = String::with_capacity(value.len());
+    let mut rest = value;
+
+    'outer: while !rest.is_empty() {
+        for (prefix, replacement) in prefixes {
+            if let Some(after) = rest.strip_prefix(prefix.as_str()) {
+                let whole = after
+                    .chars()
+                    .next()
+                    .is_none_or(|c| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')));
+                if whole {
+                    rewritten.push_str(replacement);
+                    rest = after;
+                    continue 'outer;
+                }
+            }
+        }
+
+        let c = rest.chars().next().unwrap();
+        rewritten.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    rewritten
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_transforms() {
+        assert_eq!("trim".parse(), Ok(Transform::Trim));
+        assert_eq!("split".parse(), Ok(Transform::Split(None)));
+        assert_eq!(
+            "split=;".parse(),
+            Ok(Transform::Split(Some(";".to_string())))
+        );
+        assert!("split=".parse::<Transform>().is_err());
+        assert!("upper".parse::<Transform>().is_err());
+    }
+
+    #[test]
+    fn trims_and_splits() {
+        let transforms = [Transform::Trim, Transform::Split(Some(";".to_string()))];
+        assert_eq!(
+            apply_transforms(" 8.0;9.0;;9.0a ".to_string(), &transforms),
+            EnvValue::List(vec!["8.0".into(), "9.0".into(), "9.0a".into()])
+        );
+
+        assert_eq!(
+            apply_transforms("  -O2 ".to_string(), &[Transform::Trim]),
+            EnvValue::String("-O2".to_string())
+        );
+        assert_eq!(
+            apply_transforms(String::new(), &[Transform::Split(None)]),
+            EnvValue::List(vec![])
+        );
+    }
+
+    #[test]
+    fn rewrites_whole_path_prefixes() {
+        let prefixes = [
+            ("/home/runner/work/torch".to_string(), "<workspace>"),
+            ("/home/runner".to_string(), "<home>"),
+        ];
+
+        assert_eq!(
+            rewrite_paths(
+                "-DX=/home/runner/work/torch/build -DY=/home/runner/.cache -DZ=/home/runner2",
+                &prefixes
+            ),
+            "-DX=<workspace>/build -DY=<home>/.cache -DZ=/home/runner2"
+        );
+    }
+}
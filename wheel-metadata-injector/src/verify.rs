@@ -0,0 +1,439 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Read, Seek};
+use std::str::FromStr;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use common::read_entry_to_string;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use zip::ZipArchive;
+
+use crate::limits::ArchiveLimits;
+use crate::record::Record;
+
+/// How strictly RECORD is checked against the archive contents
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecordVerification {
+    /// Refuse to process a wheel whose RECORD does not match its contents.
+    #[default]
+    Strict,
+    /// Print a warning for every mismatch and carry on.
+    Warn,
+    /// Skip verification entirely.
+    Off,
+}
+
+impl FromStr for RecordVerification {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(RecordVerification::Strict),
+            "warn" => Ok(RecordVerification::Warn),
+            "off" => Ok(RecordVerification::Off),
+            _ => Err(format!(
+                "invalid RECORD verification mode '{}', expected one of: strict, warn, off",
+                s
+            )),
+        }
+    }
+}
+
+/// A discrepancy between RECORD and the archive it describes
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RecordProblem {
+    /// A hash that is not of the `algorithm=digest` form.
+    MalformedHash { path: String },
+    /// A file other than RECORD (or its signatures) listed without a hash.
+    MissingHash { path: String },
+    /// A hash using an algorithm the wheel spec does not allow.
+    UnsupportedHash { path: String, algorithm: String },
+    /// The file's digest does not match the recorded hash.
+    HashMismatch { path: String },
+    /// The file's size does not match the recorded size.
+    SizeMismatch {
+        path: String,
+        expected: u64,
+        actual: u64,
+    },
+    /// A file listed in RECORD that is not in the archive.
+    MissingFromArchive { path: String },
+    /// A file in the archive that is not listed in RECORD.
+    NotInRecord { path: String },
+}
+
+impl RecordProblem {
+    /// The archive path the problem refers to
+    pub fn path(&self) -> &str {
+        match self {
+            RecordProblem::MalformedHash { path }
+            | RecordProblem::MissingHash { path }
+            | RecordProblem::UnsupportedHash { path, .. }
+            | RecordProblem::HashMismatch { path }
+            | RecordProblem::SizeMismatch { path, .. }
+            | RecordProblem::MissingFromArchive { path }
+            | RecordProblem::NotInRecord { path } => path,
+        }
+    }
+}
+
+impl fmt::Display for RecordProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordProblem::MalformedHash { path } => {
+                write!(f, "{}: malformed hash in RECORD", path)
+            }
+            RecordProblem::MissingHash { path } => write!(f, "{}: no hash in RECORD", path),
+            RecordProblem::UnsupportedHash { path, algorithm } => {
+                write!(f, "{}: unsupported hash algorithm '{}'", path, algorithm)
+            }
+            RecordProblem::HashMismatch { path } => {
+                write!(f, "{}: hash does not match RECORD", path)
+            }
+            RecordProblem::SizeMismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: size {} does not match RECORD ({})",
+                path, actual, expected
+            ),
+            RecordProblem::MissingFromArchive { path } => {
+                write!(f, "{}: listed in RECORD but missing from the wheel", path)
+            }
+            RecordProblem::NotInRecord { path } => {
+                write!(f, "{}: present in the wheel but not listed in RECORD", path)
+            }
+        }
+    }
+}
+
+/// Check every line of `{dist_info_dir}/RECORD` against the archive: hashes must
+/// match the entry contents, sizes must match, and every file must be listed.
+/// Entries decompressing to more than their declared size, or to more than
+/// `limits.max_total_size` in total, are an error rather than a problem.
+pub fn verify_record<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    dist_info_dir: &str,
+    limits: &ArchiveLimits,
+) -> anyhow::Result<Vec<RecordProblem>> {
+    check_record(archive, dist_info_dir, limits, |_| true)
+}
+
+/// Like [`verify_record`], but only hash the entries in `hashed`; the others are
+/// only checked to be present and listed. For a wheel rewritten from one that was
+/// verified already, whose other entries were copied over verbatim.
+pub(crate) fn verify_record_listing<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    dist_info_dir: &str,
+    limits: &ArchiveLimits,
+    hashed: &[&str],
+) -> anyhow::Result<Vec<RecordProblem>> {
+    check_record(archive, dist_info_dir, limits, |path| {
+        hashed.contains(&path)
+    })
+}
+
+fn check_record<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    dist_info_dir: &str,
+    limits: &ArchiveLimits,
+    should_hash: impl Fn(&str) -> bool,
+) -> anyhow::Result<Vec<RecordProblem>> {
+    let record_path = format!("{}/RECORD", dist_info_dir);
+    let unhashed = [
+        record_path.clone(),
+        format!("{}/RECORD.jws", dist_info_dir),
+        format!("{}/RECORD.p7s", dist_info_dir),
+    ];
+
+    let record_content = read_entry_to_string(&mut archive.by_name(&record_path)?)?;
+
+    let record = Record::parse(&record_content)
+        .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", record_path, e))?;
+
+    let mut problems = Vec::new();
+    let mut listed = HashSet::new();
+    let mut remaining = limits.max_total_size;
+
+    for entry in record.entries() {
+        let path = entry.path.as_str();
+        listed.insert(path);
+
+        if archive.index_for_name(path).is_none() {
+            problems.push(RecordProblem::MissingFromArchive {
+                path: path.to_string(),
+            });
+            continue;
+        }
+        if !should_hash(path) {
+            continue;
+        }
+
+        let hash = match &entry.hash {
+            Some(hash) => hash,
+            None => {
+                if !unhashed.iter().any(|p| p == path) {
+                    problems.push(RecordProblem::MissingHash {
+                        path: path.to_string(),
+                    });
+                }
+                continue;
+            }
+        };
+
+        let (algorithm, expected_digest) = match hash.split_once('=') {
+            Some(parts) => parts,
+            None => {
+                problems.push(RecordProblem::MalformedHash {
+                    path: path.to_string(),
+                });
+                continue;
+            }
+        };
+
+        let mut file = archive.by_name(path)?;
+        // The declared sizes were checked up front, but the decompressed stream is
+        // what actually gets read, so never read past them
+        let declared_size = file.size();
+        let mut reader = (&mut file).take(declared_size.min(remaining).saturating_add(1));
+        let (digest, actual_size) = match algorithm {
+            "sha256" => hash_reader::<Sha256>(&mut reader)?,
+            "sha384" => hash_reader::<Sha384>(&mut reader)?,
+            "sha512" => hash_reader::<Sha512>(&mut reader)?,
+            _ => {
+                problems.push(RecordProblem::UnsupportedHash {
+                    path: path.to_string(),
+                    algorithm: algorithm.to_string(),
+                });
+                continue;
+            }
+        };
+        if actual_size > declared_size {
+            anyhow::bail!(
+                "Wheel entry '{}' decompresses to more than its declared size of {} bytes",
+                path,
+                declared_size
+            );
+        }
+        if actual_size > remaining {
+            anyhow::bail!(
+                "Wheel contents exceed the uncompressed size limit of {} bytes",
+                limits.max_total_size
+            );
+        }
+        remaining -= actual_size;
+
+        if decode_record_digest(expected_digest, digest.len()).as_deref() != Some(&digest[..]) {
+            problems.push(RecordProblem::HashMismatch {
+                path: path.to_string(),
+            });
+        }
+
+        if let Some(expected) = entry.size
+            && expected != actual_size
+        {
+            problems.push(RecordProblem::SizeMismatch {
+                path: path.to_string(),
+                expected,
+                actual: actual_size,
+            });
+        }
+    }
+
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        if file.is_dir() || listed.contains(file.name()) {
+            continue;
+        }
+        problems.push(RecordProblem::NotInRecord {
+            path: file.name().to_string(),
+        });
+    }
+
+    Ok(problems)
+}
+
+/// Encode a digest for RECORD in the `urlsafe-base64-nopad` form of the wheel spec
+pub fn encode_record_digest(digest: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(digest)
+}
+
+/// Decode a RECORD digest of `digest_len` bytes. Besides the form required by the
+/// wheel spec this accepts padded base64 and the hex digests written by earlier
+/// versions of this tool, which are told apart by their length.
+pub fn decode_record_digest(value: &str, digest_len: usize) -> Option<Vec<u8>> {
+    if let Ok(digest) = URL_SAFE_NO_PAD.decode(value.trim_end_matches('='))
+        && digest.len() == digest_len
+    {
+        return Some(digest);
+    }
+
+    if value.len() == digest_len * 2 {
+        return hex::decode(value).ok();
+    }
+
+    None
+}
+
+/// Digest everything `reader` yields, returning the digest and the number of bytes
+fn hash_reader<D: Digest + io::Write>(reader: &mut impl Read) -> io::Result<(Vec<u8>, u64)> {
+    let mut hasher = D::new();
+    let size = io::copy(reader, &mut hasher)?;
+
+    Ok((hasher.finalize().to_vec(), size))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+    use crate::record::RecordEntry;
+
+    const RECORD_PATH: &str = "demo-1.0.dist-info/RECORD";
+
+    /// An archive with `files` followed by `record` as RECORD
+    fn archive(files: &[(&str, &[u8])], record: &str) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files.iter().chain([&(RECORD_PATH, record.as_bytes())]) {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    fn record_line(path: &str, data: &[u8]) -> String {
+        RecordEntry::for_data(path, data).to_line().unwrap() + "\n"
+    }
+
+    fn verify(archive: &mut ZipArchive<Cursor<Vec<u8>>>) -> Vec<RecordProblem> {
+        verify_record(archive, "demo-1.0.dist-info", &ArchiveLimits::default()).unwrap()
+    }
+
+    #[test]
+    fn decodes_spec_and_legacy_digests() {
+        let digest = Sha256::digest(b"hello").to_vec();
+
+        let unpadded = encode_record_digest(&digest);
+        assert_eq!(decode_record_digest(&unpadded, 32), Some(digest.clone()));
+        assert_eq!(
+            decode_record_digest(&format!("{}=", unpadded), 32),
+            Some(digest.clone())
+        );
+        assert_eq!(
+            decode_record_digest(&hex::encode(&digest), 32),
+            Some(digest.clone())
+        );
+
+        // A valid digest of another algorithm's length is not a match
+        assert_eq!(decode_record_digest(&unpadded, 48), None);
+        assert_eq!(decode_record_digest(&hex::encode(&digest[..16]), 32), None);
+    }
+
+    #[test]
+    fn accepts_matching_record() {
+        let init = b"__version__ = \"1.0\"\n";
+        let record = record_line("demo/__init__.py", init)
+            + &format!(
+                "demo/data.txt,sha256={},5\n",
+                hex::encode(Sha256::digest(b"hello"))
+            )
+            + RECORD_PATH
+            + ",,\n";
+        let mut archive = archive(
+            &[("demo/__init__.py", init), ("demo/data.txt", b"hello")],
+            &record,
+        );
+
+        assert_eq!(verify(&mut archive), []);
+    }
+
+    #[test]
+    fn reports_unlisted_and_missing_files() {
+        let record = record_line("demo/__init__.py", b"")
+            + &record_line("demo/gone.py", b"x")
+            + RECORD_PATH
+            + ",,\n";
+        let mut archive = archive(
+            &[("demo/__init__.py", b""), ("demo/extra.py", b"")],
+            &record,
+        );
+
+        assert_eq!(
+            verify(&mut archive),
+            [
+                RecordProblem::MissingFromArchive {
+                    path: "demo/gone.py".to_string()
+                },
+                RecordProblem::NotInRecord {
+                    path: "demo/extra.py".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_changed_contents() {
+        let record = record_line("demo/__init__.py", b"original")
+            + "demo/data.txt,,\n"
+            + RECORD_PATH
+            + ",,\n";
+        let mut archive = archive(
+            &[("demo/__init__.py", b"changed!!"), ("demo/data.txt", b"")],
+            &record,
+        );
+
+        assert_eq!(
+            verify(&mut archive),
+            [
+                RecordProblem::HashMismatch {
+                    path: "demo/__init__.py".to_string()
+                },
+                RecordProblem::SizeMismatch {
+                    path: "demo/__init__.py".to_string(),
+                    expected: 8,
+                    actual: 9,
+                },
+                RecordProblem::MissingHash {
+                    path: "demo/data.txt".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn listing_check_only_hashes_given_entries() {
+        let record = record_line("demo/__init__.py", b"original")
+            + &record_line("demo/data.txt", b"original")
+            + RECORD_PATH
+            + ",,\n";
+        let mut archive = archive(
+            &[
+                ("demo/__init__.py", b"changed"),
+                ("demo/data.txt", b"changed"),
+            ],
+            &record,
+        );
+
+        let problems = verify_record_listing(
+            &mut archive,
+            "demo-1.0.dist-info",
+            &ArchiveLimits::default(),
+            &["demo/data.txt"],
+        )
+        .unwrap();
+        assert_eq!(
+            problems.iter().map(RecordProblem::path).collect::<Vec<_>>(),
+            ["demo/data.txt", "demo/data.txt"]
+        );
+    }
+}
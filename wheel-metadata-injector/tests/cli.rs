@@ -0,0 +1,105 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+use wheel_metadata_injector::RecordEntry;
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+/// Write `demo-1.0-py3-none-any.whl` with a valid RECORD to `dir`
+fn write_wheel(dir: &Path) -> PathBuf {
+    let init: &[u8] = b"";
+    let record = format!(
+        "{}\ndemo-1.0.dist-info/RECORD,,\n",
+        RecordEntry::for_data("demo/__init__.py", init)
+            .to_line()
+            .unwrap()
+    );
+
+    let path = dir.join("demo-1.0-py3-none-any.whl");
+    let mut writer = ZipWriter::new(File::create(&path).unwrap());
+    for (name, data) in [
+        ("demo/__init__.py", init),
+        ("demo-1.0.dist-info/RECORD", record.as_bytes()),
+    ] {
+        writer
+            .start_file(name, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(data).unwrap();
+    }
+    writer.finish().unwrap();
+
+    path
+}
+
+/// A project directory whose pyproject.toml has the given tool table
+fn project(tool_table: &str) -> tempfile::TempDir {
+    let dir = tempdir().unwrap();
+    fs::write(
+        dir.path().join("pyproject.toml"),
+        format!("[tool.wheel-metadata-injector]\n{}", tool_table),
+    )
+    .unwrap();
+    write_wheel(dir.path());
+    dir
+}
+
+fn injector(dir: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("wheel-metadata-injector").unwrap();
+    cmd.current_dir(dir)
+        .args(["demo-1.0-py3-none-any.whl", "--dry-run"]);
+    cmd
+}
+
+#[test]
+fn env_vars_flag_overrides_config() {
+    let dir = project("env-vars = [\"WMI_TEST_CONFIG_VAR\"]\n");
+
+    injector(dir.path())
+        .env("WMI_TEST_CONFIG_VAR", "from-config")
+        .env("WMI_TEST_FLAG_VAR", "from-flag")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "WMI_TEST_CONFIG_VAR = \"from-config\"",
+        ));
+
+    injector(dir.path())
+        .env("WMI_TEST_CONFIG_VAR", "from-config")
+        .env("WMI_TEST_FLAG_VAR", "from-flag")
+        .args(["--env-vars", "WMI_TEST_FLAG_VAR"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("env_source = \"inline\""))
+        .stdout(predicate::str::contains(
+            "WMI_TEST_FLAG_VAR = \"from-flag\"",
+        ))
+        .stdout(predicate::str::contains("WMI_TEST_CONFIG_VAR").not());
+}
+
+#[test]
+fn redact_flag_overrides_config() {
+    let dir = project("env-vars = [\"WMI_TEST_TOKEN\"]\nredact = \"refuse\"\n");
+
+    injector(dir.path())
+        .env("WMI_TEST_TOKEN", "hunter2")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("WMI_TEST_TOKEN"));
+
+    injector(dir.path())
+        .env("WMI_TEST_TOKEN", "hunter2")
+        .args(["--redact", "off"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("WMI_TEST_TOKEN = \"hunter2\""));
+
+    injector(dir.path())
+        .env("WMI_TEST_TOKEN", "hunter2")
+        .arg("--no-config")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("WMI_TEST_TOKEN").not());
+}